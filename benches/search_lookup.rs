@@ -0,0 +1,116 @@
+//! Compares the two ways `search` can read the rows of its hits: one query
+//! per hit, as it used to, and a single `WHERE chunk_id IN (...)` query for
+//! the whole request.
+//!
+//! Run with `cargo bench --bench search_lookup`.
+
+use std::time::{Duration, Instant};
+
+use async_sqlite::rusqlite::{params, params_from_iter};
+use async_sqlite::{JournalMode, Pool, PoolBuilder};
+
+const CHUNKS: i64 = 20_000;
+const ITERATIONS: u32 = 50;
+
+/// Deterministic pseudo-random chunk ids, as a search would return them.
+fn hit_ids(count: usize) -> Vec<i64> {
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    (0..count)
+        .map(|_| {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            (state >> 33) as i64 % CHUNKS + 1
+        })
+        .collect()
+}
+
+async fn setup(path: &str) -> Pool {
+    let pool = PoolBuilder::new().path(path).journal_mode(JournalMode::Wal).open().await.unwrap();
+    pool.conn_mut(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "CREATE TABLE chunks_bench (chunk_id INTEGER PRIMARY KEY AUTOINCREMENT, text TEXT, metadata TEXT)",
+            [],
+        )?;
+        {
+            let mut insert = tx.prepare("INSERT INTO chunks_bench (text, metadata) VALUES (?, ?)")?;
+            for i in 0..CHUNKS {
+                let text = format!("chunk {} {}", i, "lorem ipsum dolor sit amet ".repeat(8));
+                let metadata = format!("{{\"source\": \"doc{}\", \"page\": {}}}", i / 20, i % 20);
+                insert.execute(params![text, metadata])?;
+            }
+        }
+        tx.commit()
+    })
+    .await
+    .unwrap();
+    pool
+}
+
+async fn per_hit(pool: &Pool, ids: &[i64]) -> usize {
+    let mut rows = 0;
+    for &chunk_id in ids {
+        let row = pool
+            .conn(move |conn| {
+                conn.query_row(
+                    "SELECT text, metadata FROM chunks_bench WHERE chunk_id = ?",
+                    [chunk_id],
+                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+                )
+            })
+            .await
+            .unwrap();
+        rows += (!row.0.is_empty()) as usize;
+    }
+    rows
+}
+
+async fn batched(pool: &Pool, ids: &[i64]) -> usize {
+    let ids = ids.to_vec();
+    pool.conn(move |conn| {
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        let mut statement = conn.prepare(&format!(
+            "SELECT chunk_id, text, metadata FROM chunks_bench WHERE chunk_id IN ({})",
+            placeholders
+        ))?;
+        let rows = statement.query_map(params_from_iter(&ids), |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?))
+        })?;
+        rows.collect::<Result<Vec<_>, _>>()
+    })
+    .await
+    .unwrap()
+    .len()
+}
+
+fn report(name: &str, elapsed: Duration) {
+    println!("{:<10} {:>10.3} ms", name, elapsed.as_secs_f64() * 1000.0 / ITERATIONS as f64);
+}
+
+#[tokio::main]
+async fn main() {
+    let path = std::env::temp_dir().join(format!("memista-bench-{}.db", std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+    let pool = setup(&path).await;
+
+    for (queries, results) in [(1, 10), (1, 50), (10, 50)] {
+        let ids = hit_ids(queries * results);
+        println!("{} queries x {} results", queries, results);
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            assert_eq!(per_hit(&pool, &ids).await, ids.len());
+        }
+        report("per hit", start.elapsed());
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            assert!(batched(&pool, &ids).await > 0);
+        }
+        report("batched", start.elapsed());
+    }
+
+    pool.close().await.unwrap();
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+    }
+}
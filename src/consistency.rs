@@ -0,0 +1,80 @@
+use std::collections::HashSet;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Ways in which a database's chunk rows and index keys have drifted apart,
+/// e.g. after a crash between the SQLite write and the index save.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Discrepancies {
+    /// Chunks without a vector in the index, which searches never return.
+    pub orphan_rows: Vec<i64>,
+    /// Index keys without a chunk row, which searches return but cannot
+    /// resolve.
+    pub orphan_keys: Vec<i64>,
+    /// Chunks with more than one vector in the index.
+    pub duplicate_keys: Vec<i64>,
+    /// Vectors under keys that were never chunk ids. They are only noticed
+    /// through the index size and cannot be removed by key.
+    pub unaccounted_vectors: usize,
+}
+
+impl Discrepancies {
+    /// Compares `rows`, the chunk ids in the table, with an index holding
+    /// `index_size` vectors, `vectors_under(key)` of them under `key`.
+    ///
+    /// The index cannot list its keys, but keys are chunk ids and those are
+    /// never reused, so every key that can legitimately appear lies between 1
+    /// and `max_id`, the largest id handed out so far.
+    pub fn find(rows: &[i64], max_id: i64, vectors_under: impl Fn(i64) -> usize, index_size: usize) -> Self {
+        let mut discrepancies = Discrepancies::default();
+        let mut accounted = 0;
+        let rows: HashSet<i64> = rows.iter().copied().collect();
+        for key in 1..=max_id {
+            let vectors = vectors_under(key);
+            accounted += vectors;
+            match (rows.contains(&key), vectors) {
+                (true, 0) => discrepancies.orphan_rows.push(key),
+                (true, 1) | (false, 0) => {}
+                (true, _) => discrepancies.duplicate_keys.push(key),
+                (false, _) => discrepancies.orphan_keys.push(key),
+            }
+        }
+        discrepancies.unaccounted_vectors = index_size.saturating_sub(accounted);
+        discrepancies
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orphan_rows.is_empty()
+            && self.orphan_keys.is_empty()
+            && self.duplicate_keys.is_empty()
+            && self.unaccounted_vectors == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn finds_drift_between_rows_and_keys() {
+        let keys: HashMap<i64, usize> = [(1, 1), (2, 2), (4, 1), (6, 1)].into_iter().collect();
+        let vectors_under = |key| keys.get(&key).copied().unwrap_or(0);
+
+        let discrepancies = Discrepancies::find(&[1, 2, 3], 6, vectors_under, 7);
+        assert_eq!(
+            discrepancies,
+            Discrepancies {
+                orphan_rows: vec![3],
+                orphan_keys: vec![4, 6],
+                duplicate_keys: vec![2],
+                unaccounted_vectors: 2,
+            }
+        );
+        assert!(!discrepancies.is_empty());
+
+        let consistent = Discrepancies::find(&[1, 4, 6], 6, |key| [1, 4, 6].contains(&key) as usize, 3);
+        assert!(consistent.is_empty());
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Position in the results of a search, handed out as an opaque token.
+///
+/// A cursor records the database revision it was issued at, so following it
+/// after the database changed can be detected instead of silently skipping or
+/// repeating hits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub revision: u64,
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCursor;
+
+impl fmt::Display for InvalidCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid cursor")
+    }
+}
+
+impl std::error::Error for InvalidCursor {}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}{:016x}", self.revision, self.offset as u64)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = InvalidCursor;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.len() != 32 || !value.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(InvalidCursor);
+        }
+        let revision = u64::from_str_radix(&value[..16], 16).map_err(|_| InvalidCursor)?;
+        let offset = u64::from_str_radix(&value[16..], 16).map_err(|_| InvalidCursor)?;
+        Ok(Cursor {
+            revision,
+            offset: usize::try_from(offset).map_err(|_| InvalidCursor)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let cursor = Cursor { revision: 42, offset: 20 };
+        let token = cursor.to_string();
+        assert_eq!(token.len(), 32);
+        assert_eq!(token.parse::<Cursor>().unwrap(), cursor);
+    }
+
+    #[test]
+    fn rejects_malformed_tokens() {
+        for token in ["", "42", "zz00000000000000000000000000000a", "+0000000000000000000000000000000a", &"0".repeat(33)] {
+            assert_eq!(token.parse::<Cursor>(), Err(InvalidCursor), "{:?}", token);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt;
+use std::str::FromStr;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Longest accepted database id.
+pub const MAX_LEN: usize = 64;
+
+/// Identifier of a database.
+///
+/// Ids are interpolated into SQL table names and index file paths, so they are
+/// restricted to 1 to 64 ASCII letters, digits and underscores. Every request
+/// carrying an id parses it into this type before touching storage.
+///
+/// SQLite table names ignore case, so ids are lowercased: `MyDb` and `mydb`
+/// name the same database rather than two sharing one chunk table.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(try_from = "String", into = "String")]
+pub struct DatabaseId(#[schemars(regex(pattern = r"^[A-Za-z0-9_]{1,64}$"))] String);
+
+impl DatabaseId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Name of the SQLite table holding the database's chunks.
+    pub fn table_name(&self) -> String {
+        format!("chunks_{}", self.0)
+    }
+
+    /// Path of the database's usearch index file.
+    pub fn index_file(&self) -> String {
+        format!("{}.usearch", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidDatabaseId(String);
+
+impl fmt::Display for InvalidDatabaseId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid database id {:?}: expected 1 to {} ASCII letters, digits or underscores",
+            self.0, MAX_LEN
+        )
+    }
+}
+
+impl std::error::Error for InvalidDatabaseId {}
+
+impl TryFrom<String> for DatabaseId {
+    type Error = InvalidDatabaseId;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let valid = !value.is_empty()
+            && value.len() <= MAX_LEN
+            && value.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'_');
+        if valid {
+            Ok(DatabaseId(value.to_ascii_lowercase()))
+        } else {
+            Err(InvalidDatabaseId(value))
+        }
+    }
+}
+
+impl FromStr for DatabaseId {
+    type Err = InvalidDatabaseId;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        DatabaseId::try_from(value.to_string())
+    }
+}
+
+impl From<DatabaseId> for String {
+    fn from(id: DatabaseId) -> String {
+        id.0
+    }
+}
+
+impl fmt::Display for DatabaseId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_plain_identifiers() {
+        for id in ["my_db", "a", "tenant42", "_", &"x".repeat(MAX_LEN)] {
+            let parsed: DatabaseId = id.parse().unwrap();
+            assert_eq!(parsed.as_str(), id);
+        }
+    }
+
+    #[test]
+    fn folds_case() {
+        let mixed: DatabaseId = "MyDb".parse().unwrap();
+        let lower: DatabaseId = "mydb".parse().unwrap();
+        assert_eq!(mixed, lower);
+        assert_eq!(mixed.table_name(), "chunks_mydb");
+        assert_eq!(mixed.index_file(), "mydb.usearch");
+    }
+
+    #[test]
+    fn derives_table_and_file_names() {
+        let id: DatabaseId = "my_db".parse().unwrap();
+        assert_eq!(id.table_name(), "chunks_my_db");
+        assert_eq!(id.index_file(), "my_db.usearch");
+    }
+
+    #[test]
+    fn rejects_hostile_identifiers() {
+        let hostile = [
+            "",
+            "x; DROP TABLE databases; --",
+            "x DROP",
+            "x'--",
+            "x\"y",
+            "../../etc/foo",
+            "..",
+            "a/b",
+            "a\\b",
+            "/abs",
+            "name.usearch",
+            "tab\tle",
+            "new\nline",
+            "nul\0byte",
+            "dash-ed",
+            "ümlaut",
+            "ｆｕｌｌｗｉｄｔｈ",
+        ];
+        for id in hostile {
+            assert!(id.parse::<DatabaseId>().is_err(), "{:?} should be rejected", id);
+        }
+        assert!("x".repeat(MAX_LEN + 1).parse::<DatabaseId>().is_err());
+    }
+
+    #[test]
+    fn validates_when_deserializing() {
+        let parsed: DatabaseId = serde_json::from_str("\"my_db\"").unwrap();
+        assert_eq!(parsed.as_str(), "my_db");
+        assert!(serde_json::from_str::<DatabaseId>("\"../etc/passwd\"").is_err());
+        assert!(serde_json::from_str::<DatabaseId>("\"x; DROP TABLE t\"").is_err());
+    }
+}
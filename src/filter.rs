@@ -0,0 +1,219 @@
+use std::fmt;
+
+use async_sqlite::rusqlite::types::Value as SqlValue;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A comparison between a metadata field and a JSON value.
+///
+/// `field` is a dot-separated path into the metadata object, e.g. `author.name`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Comparison {
+    pub field: String,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Membership {
+    pub field: String,
+    pub values: Vec<Value>,
+}
+
+/// Boolean expression over chunk metadata, e.g.
+/// `{"and": [{"eq": {"field": "source", "value": "doc1"}}, {"gte": {"field": "page", "value": 3}}]}`.
+///
+/// Comparisons against a field the metadata lacks are false, except `ne`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+    Eq(Comparison),
+    Ne(Comparison),
+    Gt(Comparison),
+    Gte(Comparison),
+    Lt(Comparison),
+    Lte(Comparison),
+    In(Membership),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterError(String);
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid filter: {}", self.0)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+/// Value of the field at the bound JSON path, NULL for metadata that is not
+/// JSON, such as plain strings stored before metadata had to be an object.
+const FIELD: &str = "(CASE WHEN json_valid(metadata) THEN json_extract(metadata, ?) END)";
+
+impl Filter {
+    /// Renders the filter as an SQL condition on the `metadata` column,
+    /// appending its bind parameters to `params`. The condition is never
+    /// NULL, so `not` behaves as expected on missing fields.
+    pub fn to_sql(&self, params: &mut Vec<SqlValue>) -> Result<String, FilterError> {
+        match self {
+            Filter::And(filters) => Self::join(filters, " AND ", "1", params),
+            Filter::Or(filters) => Self::join(filters, " OR ", "0", params),
+            Filter::Not(filter) => Ok(format!("NOT ({})", filter.to_sql(params)?)),
+            Filter::Eq(comparison) if comparison.value.is_null() => {
+                params.push(json_path(&comparison.field)?);
+                Ok(format!("({} IS NULL)", FIELD))
+            }
+            Filter::Ne(comparison) if comparison.value.is_null() => {
+                params.push(json_path(&comparison.field)?);
+                Ok(format!("({} IS NOT NULL)", FIELD))
+            }
+            Filter::Ne(comparison) => {
+                params.push(json_path(&comparison.field)?);
+                params.push(scalar(&comparison.value)?);
+                Ok(format!("({} IS NOT ?)", FIELD))
+            }
+            Filter::Eq(comparison) => Self::compare(comparison, "=", params),
+            Filter::Gt(comparison) => Self::compare(comparison, ">", params),
+            Filter::Gte(comparison) => Self::compare(comparison, ">=", params),
+            Filter::Lt(comparison) => Self::compare(comparison, "<", params),
+            Filter::Lte(comparison) => Self::compare(comparison, "<=", params),
+            Filter::In(membership) => {
+                if membership.values.is_empty() {
+                    return Ok("0".to_string());
+                }
+                params.push(json_path(&membership.field)?);
+                for value in &membership.values {
+                    params.push(scalar(value)?);
+                }
+                let placeholders = vec!["?"; membership.values.len()].join(", ");
+                Ok(format!("COALESCE({} IN ({}), 0)", FIELD, placeholders))
+            }
+        }
+    }
+
+    fn join(filters: &[Filter], separator: &str, empty: &str, params: &mut Vec<SqlValue>) -> Result<String, FilterError> {
+        if filters.is_empty() {
+            return Ok(empty.to_string());
+        }
+        let parts = filters
+            .iter()
+            .map(|filter| filter.to_sql(params).map(|sql| format!("({})", sql)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(parts.join(separator))
+    }
+
+    fn compare(comparison: &Comparison, operator: &str, params: &mut Vec<SqlValue>) -> Result<String, FilterError> {
+        params.push(json_path(&comparison.field)?);
+        params.push(scalar(&comparison.value)?);
+        Ok(format!("COALESCE({} {} ?, 0)", FIELD, operator))
+    }
+}
+
+/// Turns `a.b` into the JSON path `$."a"."b"`.
+pub fn json_path(field: &str) -> Result<SqlValue, FilterError> {
+    let mut path = String::from("$");
+    for segment in field.split('.') {
+        let valid = !segment.is_empty()
+            && segment.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-');
+        if !valid {
+            return Err(FilterError(format!(
+                "field {:?} must be dot-separated ASCII letters, digits, underscores or hyphens",
+                field
+            )));
+        }
+        path.push_str(&format!(".\"{}\"", segment));
+    }
+    Ok(SqlValue::Text(path))
+}
+
+/// Converts a JSON scalar to the SQL value `json_extract` would return for it.
+fn scalar(value: &Value) -> Result<SqlValue, FilterError> {
+    match value {
+        Value::Null => Ok(SqlValue::Null),
+        Value::Bool(value) => Ok(SqlValue::Integer(*value as i64)),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => Ok(SqlValue::Integer(integer)),
+            None => Ok(SqlValue::Real(number.as_f64().unwrap_or(f64::NAN))),
+        },
+        Value::String(value) => Ok(SqlValue::Text(value.clone())),
+        Value::Array(_) | Value::Object(_) => Err(FilterError("only scalar values can be compared".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_sqlite::rusqlite::{params_from_iter, Connection};
+    use serde_json::json;
+
+    /// Returns the ids of the rows of a small fixture table matching `filter`.
+    fn matching(filter: Value) -> Vec<i64> {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"CREATE TABLE chunks (chunk_id INTEGER PRIMARY KEY, metadata TEXT);
+               INSERT INTO chunks VALUES
+                   (1, '{"source": "a", "page": 1, "draft": true}'),
+                   (2, '{"source": "b", "page": 5, "author": {"name": "x"}}'),
+                   (3, '{"source": "a", "page": 9.5}'),
+                   (4, NULL),
+                   (5, 'plain text');"#,
+        )
+        .unwrap();
+
+        let filter: Filter = serde_json::from_value(filter).unwrap();
+        let mut params = Vec::new();
+        let sql = filter.to_sql(&mut params).unwrap();
+        let mut statement = conn
+            .prepare(&format!("SELECT chunk_id FROM chunks WHERE {} ORDER BY chunk_id", sql))
+            .unwrap();
+        let ids = statement.query_map(params_from_iter(params), |row| row.get(0)).unwrap();
+        ids.collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn compares_fields() {
+        assert_eq!(matching(json!({"eq": {"field": "source", "value": "a"}})), vec![1, 3]);
+        assert_eq!(matching(json!({"ne": {"field": "source", "value": "a"}})), vec![2, 4, 5]);
+        assert_eq!(matching(json!({"gte": {"field": "page", "value": 5}})), vec![2, 3]);
+        assert_eq!(matching(json!({"lt": {"field": "page", "value": 5}})), vec![1]);
+        assert_eq!(matching(json!({"eq": {"field": "draft", "value": true}})), vec![1]);
+        assert_eq!(matching(json!({"eq": {"field": "author.name", "value": "x"}})), vec![2]);
+        assert_eq!(matching(json!({"in": {"field": "page", "values": [1, 9.5]}})), vec![1, 3]);
+        assert_eq!(matching(json!({"eq": {"field": "author", "value": null}})), vec![1, 3, 4, 5]);
+    }
+
+    #[test]
+    fn combines_conditions() {
+        let filter = json!({"and": [
+            {"eq": {"field": "source", "value": "a"}},
+            {"not": {"lt": {"field": "page", "value": 5}}},
+        ]});
+        assert_eq!(matching(filter), vec![3]);
+
+        let filter = json!({"or": [
+            {"eq": {"field": "source", "value": "b"}},
+            {"eq": {"field": "draft", "value": true}},
+        ]});
+        assert_eq!(matching(filter), vec![1, 2]);
+
+        // Missing fields make comparisons false, so negating them is true.
+        assert_eq!(matching(json!({"not": {"gt": {"field": "page", "value": 2}}})), vec![1, 4, 5]);
+        assert_eq!(matching(json!({"and": []})), vec![1, 2, 3, 4, 5]);
+        assert_eq!(matching(json!({"or": []})), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn rejects_invalid_filters() {
+        let mut params = Vec::new();
+        let bad_field = Filter::Eq(Comparison { field: "a\"); DROP TABLE x; --".to_string(), value: json!(1) });
+        assert!(bad_field.to_sql(&mut params).is_err());
+        let empty_segment = Filter::Eq(Comparison { field: "a..b".to_string(), value: json!(1) });
+        assert!(empty_segment.to_sql(&mut params).is_err());
+        let object_value = Filter::Gt(Comparison { field: "a".to_string(), value: json!({"b": 1}) });
+        assert!(object_value.to_sql(&mut params).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use async_sqlite::rusqlite::{self, Connection, OptionalExtension};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::database_id::DatabaseId;
+
+/// Quoted name of the FTS5 table indexing a database's chunk text.
+///
+/// The colon cannot appear in a database id, so the name and the shadow
+/// tables FTS5 derives from it never collide with another database's tables.
+pub fn text_index_table(database_id: &DatabaseId) -> String {
+    format!("\"{}:fts\"", database_id.table_name())
+}
+
+/// Creates the FTS5 index over `text` of the database's chunk table, and the
+/// triggers keeping it in sync. An index added to an existing table is
+/// populated from its rows.
+pub fn create_text_index(conn: &Connection, database_id: &DatabaseId) -> rusqlite::Result<()> {
+    let table = database_id.table_name();
+    let name = format!("{}:fts", table);
+    let exists = conn
+        .query_row("SELECT 1 FROM sqlite_master WHERE name = ?", [&name], |_| Ok(()))
+        .optional()?
+        .is_some();
+    if exists {
+        return Ok(());
+    }
+
+    conn.execute_batch(&format!(
+        r#"CREATE VIRTUAL TABLE "{name}" USING fts5(text, content = '{table}', content_rowid = 'chunk_id');
+           CREATE TRIGGER "{name}_insert" AFTER INSERT ON {table} BEGIN
+               INSERT INTO "{name}" (rowid, text) VALUES (new.chunk_id, new.text);
+           END;
+           CREATE TRIGGER "{name}_delete" AFTER DELETE ON {table} BEGIN
+               INSERT INTO "{name}" ("{name}", rowid, text) VALUES ('delete', old.chunk_id, old.text);
+           END;
+           CREATE TRIGGER "{name}_update" AFTER UPDATE OF text ON {table} BEGIN
+               INSERT INTO "{name}" ("{name}", rowid, text) VALUES ('delete', old.chunk_id, old.text);
+               INSERT INTO "{name}" (rowid, text) VALUES (new.chunk_id, new.text);
+           END;
+           INSERT INTO "{name}" ("{name}") VALUES ('rebuild');"#,
+        name = name,
+        table = table,
+    ))
+}
+
+/// Turns free text into an FTS5 query matching any of its terms. Each
+/// whitespace-separated term is quoted, so identifiers such as `AB-1234` are
+/// matched as written rather than parsed as query syntax. Returns `None` if
+/// the text has no terms.
+pub fn keyword_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" OR "))
+    }
+}
+
+/// Whether `error`, raised while running a full-text search statement, was
+/// caused by the FTS5 query the caller wrote, such as unbalanced quotes or an
+/// unknown column filter. FTS5 reports those as generic SQLite errors, so they
+/// are told apart by the messages its query parser raises.
+pub fn is_query_error(error: &rusqlite::Error) -> bool {
+    match error {
+        rusqlite::Error::SqliteFailure(error, Some(message)) if error.code == rusqlite::ErrorCode::Unknown => {
+            message.starts_with("fts5")
+                || message.starts_with("no such column: ")
+                || message.starts_with("unknown special query: ")
+                || message == "unterminated string"
+        }
+        _ => false,
+    }
+}
+
+fn default_snippet_start() -> String {
+    "<b>".to_string()
+}
+
+fn default_snippet_end() -> String {
+    "</b>".to_string()
+}
+
+fn default_snippet_tokens() -> usize {
+    16
+}
+
+/// How matched terms are highlighted in the snippets of a full-text search.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SnippetOptions {
+    /// Inserted before each matched term.
+    #[serde(default = "default_snippet_start")]
+    pub start: String,
+    /// Inserted after each matched term.
+    #[serde(default = "default_snippet_end")]
+    pub end: String,
+    /// Tokens of context to return, at most 64.
+    #[serde(default = "default_snippet_tokens")]
+    pub max_tokens: usize,
+}
+
+impl Default for SnippetOptions {
+    fn default() -> Self {
+        SnippetOptions {
+            start: default_snippet_start(),
+            end: default_snippet_end(),
+            max_tokens: default_snippet_tokens(),
+        }
+    }
+}
+
+fn default_rrf_k() -> f32 {
+    60.0
+}
+
+/// How the vector and keyword rankings of a hybrid search are combined.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Fusion {
+    /// Reciprocal rank fusion: a chunk scores `1 / (k + rank)` for each
+    /// ranking it appears in.
+    Rrf {
+        #[serde(default = "default_rrf_k")]
+        k: f32,
+    },
+    /// Weighted sum of the min-max normalised vector and BM25 scores.
+    Weighted {
+        /// Weight of the vector score between 0 and 1; the keyword score
+        /// gets the rest.
+        vector_weight: f32,
+    },
+}
+
+impl Default for Fusion {
+    fn default() -> Self {
+        Fusion::Rrf { k: default_rrf_k() }
+    }
+}
+
+impl Fusion {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Fusion::Rrf { k } if k.is_nan() || *k < 0.0 => Err("rrf k must not be negative".to_string()),
+            Fusion::Weighted { vector_weight } if !(0.0..=1.0).contains(vector_weight) => {
+                Err("vector_weight must be between 0 and 1".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Combines two rankings of `(chunk_id, score)`, best first, into one
+    /// holding at most `limit` chunks.
+    pub fn fuse(&self, vector: &[(u64, f32)], keyword: &[(u64, f32)], limit: usize) -> Vec<(u64, f32)> {
+        let mut scores: HashMap<u64, f32> = HashMap::new();
+        match self {
+            Fusion::Rrf { k } => {
+                for ranking in [vector, keyword] {
+                    for (rank, (chunk_id, _)) in ranking.iter().enumerate() {
+                        *scores.entry(*chunk_id).or_default() += 1.0 / (k + rank as f32 + 1.0);
+                    }
+                }
+            }
+            Fusion::Weighted { vector_weight } => {
+                for (ranking, weight) in [(vector, *vector_weight), (keyword, 1.0 - vector_weight)] {
+                    for (chunk_id, score) in normalize(ranking) {
+                        *scores.entry(chunk_id).or_default() += weight * score;
+                    }
+                }
+            }
+        }
+
+        let mut fused: Vec<(u64, f32)> = scores.into_iter().collect();
+        fused.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        fused.truncate(limit);
+        fused
+    }
+}
+
+/// Scales scores to between 0 and 1, mapping the best to 1.
+fn normalize(ranking: &[(u64, f32)]) -> Vec<(u64, f32)> {
+    let max = ranking.iter().map(|(_, score)| *score).fold(f32::NEG_INFINITY, f32::max);
+    let min = ranking.iter().map(|(_, score)| *score).fold(f32::INFINITY, f32::min);
+    ranking
+        .iter()
+        .map(|(chunk_id, score)| {
+            let normalized = if max > min { (score - min) / (max - min) } else { 1.0 };
+            (*chunk_id, normalized)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matching(conn: &Connection, database_id: &DatabaseId, text: &str) -> Vec<i64> {
+        let sql = format!("SELECT rowid FROM {0} WHERE {0} MATCH ? ORDER BY rowid", text_index_table(database_id));
+        let mut statement = conn.prepare(&sql).unwrap();
+        let ids = statement.query_map([keyword_query(text).unwrap()], |row| row.get(0)).unwrap();
+        ids.collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn keeps_text_index_in_sync() {
+        let conn = Connection::open_in_memory().unwrap();
+        let database_id: DatabaseId = "docs".parse().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE chunks_docs (chunk_id INTEGER PRIMARY KEY, text TEXT, metadata TEXT);
+             INSERT INTO chunks_docs (chunk_id, text) VALUES (1, 'replacement part AB-1234');",
+        )
+        .unwrap();
+
+        // Rows that predate the index are picked up when it is created.
+        create_text_index(&conn, &database_id).unwrap();
+        create_text_index(&conn, &database_id).unwrap();
+        assert_eq!(matching(&conn, &database_id, "ab-1234"), vec![1]);
+
+        conn.execute("INSERT INTO chunks_docs (chunk_id, text) VALUES (2, 'spare part')", []).unwrap();
+        assert_eq!(matching(&conn, &database_id, "part"), vec![1, 2]);
+
+        conn.execute("UPDATE chunks_docs SET text = 'wheel' WHERE chunk_id = 1", []).unwrap();
+        conn.execute("DELETE FROM chunks_docs WHERE chunk_id = 2", []).unwrap();
+        assert_eq!(matching(&conn, &database_id, "part"), Vec::<i64>::new());
+        assert_eq!(matching(&conn, &database_id, "wheel"), vec![1]);
+    }
+
+    #[test]
+    fn quotes_keyword_terms() {
+        assert_eq!(keyword_query("AB-1234 say \"hi\"").unwrap(), r#""AB-1234" OR "say" OR """hi""""#);
+        assert_eq!(keyword_query("  "), None);
+    }
+
+    #[test]
+    fn recognises_query_errors() {
+        let conn = Connection::open_in_memory().unwrap();
+        let database_id: DatabaseId = "docs".parse().unwrap();
+        conn.execute_batch("CREATE TABLE chunks_docs (chunk_id INTEGER PRIMARY KEY, text TEXT, metadata TEXT);").unwrap();
+        create_text_index(&conn, &database_id).unwrap();
+
+        let sql = format!("SELECT rowid FROM {0} WHERE {0} MATCH ?", text_index_table(&database_id));
+        for query in ["\"unbalanced", "AND", "title:word", "a NEAR("] {
+            let error = conn.query_row(&sql, [query], |_| Ok(())).unwrap_err();
+            assert!(is_query_error(&error), "{:?} gave {:?}", query, error);
+        }
+        let missing = conn.query_row(&sql, ["\"quoted phrase\" OR pre*"], |_| Ok(())).unwrap_err();
+        assert!(!is_query_error(&missing));
+        let broken = conn.query_row("SELECT json_extract('{', '$')", [], |_| Ok(())).unwrap_err();
+        assert!(!is_query_error(&broken), "{:?}", broken);
+    }
+
+    #[test]
+    fn fuses_rankings() {
+        let vector = [(1, 0.9), (2, 0.8), (3, 0.1)];
+        let keyword = [(2, 12.0), (3, 4.0)];
+
+        let rrf = Fusion::default().fuse(&vector, &keyword, 10);
+        let order: Vec<u64> = rrf.iter().map(|(chunk_id, _)| *chunk_id).collect();
+        assert_eq!(order, vec![2, 3, 1]);
+
+        let keyword_only = Fusion::Weighted { vector_weight: 0.0 }.fuse(&vector, &keyword, 1);
+        assert_eq!(keyword_only, vec![(2, 1.0)]);
+        let vector_only = Fusion::Weighted { vector_weight: 1.0 }.fuse(&vector, &keyword, 1);
+        assert_eq!(vector_only, vec![(1, 1.0)]);
+
+        assert!(Fusion::Weighted { vector_weight: 1.5 }.validate().is_err());
+    }
+}
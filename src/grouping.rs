@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::filter;
+use crate::CANDIDATES_PER_RESULT;
+
+/// Largest candidate pool searched to fill groups.
+pub const MAX_CANDIDATES: usize = 4096;
+
+fn default_max_hits() -> usize {
+    1
+}
+
+/// Collapses hits sharing the value of a metadata field into groups.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GroupBy {
+    /// Dot-separated metadata field to group on, e.g. `source`. Hits lacking
+    /// the field form a `null` group.
+    pub field: String,
+    /// Hits kept per group, best first.
+    #[serde(default = "default_max_hits")]
+    pub max_hits: usize,
+}
+
+/// Hits sharing a value of the grouped field, best first.
+pub type Group = (Value, Vec<(u64, f32)>);
+
+impl GroupBy {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_CANDIDATES).contains(&self.max_hits) {
+            return Err(format!("group_by max_hits must be between 1 and {}", MAX_CANDIDATES));
+        }
+        filter::json_path(&self.field).map(|_| ()).map_err(|error| error.to_string())
+    }
+
+    /// Collapses a ranking of `(chunk_id, score)`, best first, into at most
+    /// `count` groups ordered by their best hit. `values` maps chunks to their
+    /// value of the grouped field.
+    pub fn group(&self, ranking: &[(u64, f32)], values: &HashMap<u64, Value>, count: usize) -> Vec<Group> {
+        let mut groups: Vec<Group> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for &(chunk_id, score) in ranking {
+            let value = values.get(&chunk_id).cloned().unwrap_or(Value::Null);
+            match positions.get(&value.to_string()) {
+                Some(&position) => {
+                    let hits = &mut groups[position].1;
+                    if hits.len() < self.max_hits {
+                        hits.push((chunk_id, score));
+                    }
+                }
+                None if groups.len() < count => {
+                    positions.insert(value.to_string(), groups.len());
+                    groups.push((value, vec![(chunk_id, score)]));
+                }
+                None => {}
+            }
+        }
+        groups
+    }
+
+    /// Size of the first candidate pool searched for `count` groups, which
+    /// grows from there up to `MAX_CANDIDATES` if the groups are not filled.
+    pub fn pool_size(&self, count: usize) -> usize {
+        count.saturating_mul(self.max_hits).saturating_mul(CANDIDATES_PER_RESULT).min(MAX_CANDIDATES)
+    }
+
+    /// Whether more candidates cannot change `groups`: there are `count` of
+    /// them and each holds `max_hits` hits.
+    pub fn is_filled(&self, groups: &[Group], count: usize) -> bool {
+        groups.len() >= count && groups.iter().all(|(_, hits)| hits.len() >= self.max_hits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn collapses_hits_by_value() {
+        let values: HashMap<u64, Value> =
+            [(1, json!("a")), (2, json!("a")), (3, json!("b")), (4, json!("a")), (6, json!("c"))].into_iter().collect();
+        let ranking = [(1, 0.9), (2, 0.8), (3, 0.7), (4, 0.6), (5, 0.5), (6, 0.4)];
+        let group_by = GroupBy { field: "source".to_string(), max_hits: 2 };
+
+        let groups = group_by.group(&ranking, &values, 3);
+        assert_eq!(
+            groups,
+            vec![
+                (json!("a"), vec![(1, 0.9), (2, 0.8)]),
+                (json!("b"), vec![(3, 0.7)]),
+                (Value::Null, vec![(5, 0.5)]),
+            ]
+        );
+        assert!(!group_by.is_filled(&groups, 3));
+        assert!(GroupBy { max_hits: 1, ..group_by.clone() }.is_filled(&groups, 3));
+        assert!(!GroupBy { max_hits: 1, ..group_by }.is_filled(&groups, 4));
+    }
+
+    #[test]
+    fn caps_the_candidate_pool() {
+        let group_by = GroupBy { field: "source".to_string(), max_hits: 2 };
+        assert_eq!(group_by.pool_size(3), 24);
+        assert_eq!(group_by.pool_size(10_001), MAX_CANDIDATES);
+        assert_eq!(GroupBy { max_hits: MAX_CANDIDATES, ..group_by }.pool_size(usize::MAX), MAX_CANDIDATES);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        assert!(GroupBy { field: "source".to_string(), max_hits: 0 }.validate().is_err());
+        assert!(GroupBy { field: "source".to_string(), max_hits: MAX_CANDIDATES + 1 }.validate().is_err());
+        assert!(GroupBy { field: "a b".to_string(), max_hits: 1 }.validate().is_err());
+        assert!(GroupBy { field: "author.name".to_string(), max_hits: 3 }.validate().is_ok());
+    }
+}
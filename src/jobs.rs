@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::database_id::DatabaseId;
+
+/// Seconds a finished job stays available to poll.
+pub const RETENTION_SECS: i64 = 60 * 60;
+
+/// Stage of a background reindex.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Reading the stored embeddings.
+    Loading,
+    /// Building the new index while the current one keeps serving.
+    Building,
+    /// Applying the writes made during the build, then swapping the indexes.
+    CatchingUp,
+    Succeeded,
+    Failed,
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobState::Succeeded | JobState::Failed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Job {
+    pub job_id: Uuid,
+    pub database_id: DatabaseId,
+    pub state: JobState,
+    /// Vectors added to the new index so far.
+    pub processed: usize,
+    /// Vectors to add, known once loading is done.
+    pub total: usize,
+    /// Why the job failed.
+    pub error: Option<String>,
+    /// Unix timestamp in seconds.
+    pub started_at: i64,
+    /// Unix timestamp in seconds, once the job succeeded or failed.
+    pub finished_at: Option<i64>,
+}
+
+/// Background jobs by id. They are only kept in memory, so a restart forgets
+/// them and abandons those still running. Finished jobs are dropped once
+/// `RETENTION_SECS` have passed, when the next job starts.
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<Uuid, Job>>,
+}
+
+impl JobRegistry {
+    /// Registers a job on `database_id` started at `now`. Fails with the id of
+    /// the running job if the database already has one.
+    pub fn start(&self, database_id: &DatabaseId, now: i64) -> Result<Job, Uuid> {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|_, job| job.finished_at.is_none_or(|finished_at| now - finished_at < RETENTION_SECS));
+        if let Some(running) = jobs.values().find(|job| &job.database_id == database_id && !job.state.is_finished()) {
+            return Err(running.job_id);
+        }
+
+        let job = Job {
+            job_id: Uuid::new_v4(),
+            database_id: database_id.clone(),
+            state: JobState::Loading,
+            processed: 0,
+            total: 0,
+            error: None,
+            started_at: now,
+            finished_at: None,
+        };
+        jobs.insert(job.job_id, job.clone());
+        Ok(job)
+    }
+
+    pub fn get(&self, job_id: Uuid) -> Option<Job> {
+        self.jobs.lock().unwrap().get(&job_id).cloned()
+    }
+
+    pub fn update(&self, job_id: Uuid, change: impl FnOnce(&mut Job)) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(&job_id) {
+            change(job);
+        }
+    }
+
+    /// Records the outcome of a job at `now`.
+    pub fn finish(&self, job_id: Uuid, result: Result<(), String>, now: i64) {
+        self.update(job_id, |job| {
+            match result {
+                Ok(()) => job.state = JobState::Succeeded,
+                Err(error) => {
+                    job.state = JobState::Failed;
+                    job.error = Some(error);
+                }
+            }
+            job.finished_at = Some(now);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_one_job_per_database() {
+        let jobs = JobRegistry::default();
+        let docs: DatabaseId = "docs".parse().unwrap();
+        let first = jobs.start(&docs, 1).unwrap();
+        assert_eq!(jobs.start(&docs, 2).unwrap_err(), first.job_id);
+        assert!(jobs.start(&"notes".parse().unwrap(), 2).is_ok());
+
+        jobs.finish(first.job_id, Err("disk full".to_string()), 3);
+        let failed = jobs.get(first.job_id).unwrap();
+        assert_eq!((failed.state, failed.error.as_deref(), failed.finished_at), (JobState::Failed, Some("disk full"), Some(3)));
+        assert!(jobs.start(&docs, 4).is_ok());
+    }
+
+    #[test]
+    fn forgets_finished_jobs_after_retention() {
+        let jobs = JobRegistry::default();
+        let docs: DatabaseId = "docs".parse().unwrap();
+        let finished = jobs.start(&docs, 0).unwrap();
+        jobs.finish(finished.job_id, Ok(()), 10);
+        let running = jobs.start(&"notes".parse().unwrap(), 10).unwrap();
+
+        jobs.start(&docs, 10 + RETENTION_SECS - 1).unwrap();
+        assert!(jobs.get(finished.job_id).is_some());
+        jobs.start(&"other".parse().unwrap(), 10 + RETENTION_SECS).unwrap();
+        assert!(jobs.get(finished.job_id).is_none());
+        assert!(jobs.get(running.job_id).is_some());
+    }
+}
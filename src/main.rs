@@ -1,93 +1,1486 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use actix_web::{web, App, HttpServer, HttpResponse};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
 use anyhow::Result;
-use usearch::{Index, IndexOptions, MetricKind, ScalarKind, new_index};
+use usearch::{b1x8, Index, IndexOptions, MetricKind, ScalarKind, new_index};
+use usearch::ffi::Matches;
+use uuid::Uuid;
 use async_sqlite::{Pool, PoolBuilder, JournalMode};
+use async_sqlite::rusqlite::{self, params, params_from_iter, OptionalExtension, Row};
+use async_sqlite::rusqlite::types::{Value as SqlValue, FromSql, FromSqlError, FromSqlResult, Type, ValueRef};
 use apistos::{api_operation, ApiComponent};
 use apistos::app::{BuildConfig, OpenApiWrapper};
 use apistos::info::Info;
 use apistos::server::Server;
 use apistos::spec::Spec;
-use apistos::web::{post, delete, resource, scope};
+use apistos::web::{get, post, put, delete, resource, scope};
 use apistos::{RapidocConfig, RedocConfig, ScalarConfig, SwaggerUIConfig};
 use schemars::JsonSchema;
 use dotenv::dotenv;
 use std::env;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use log::{debug, info, warn};
 
+mod consistency;
+mod cursor;
+mod database_id;
+mod filter;
+mod full_text;
+mod grouping;
+mod jobs;
+mod mmr;
+mod registry;
+mod undo;
+
+use consistency::Discrepancies;
+use cursor::Cursor;
+use database_id::DatabaseId;
+use filter::Filter;
+use full_text::{Fusion, SnippetOptions};
+use grouping::GroupBy;
+use jobs::{JobRegistry, JobState};
+use mmr::Mmr;
+use registry::{IndexHandle, IndexRegistry, SharedIndex};
+use undo::{RowUndo, VectorStep, VectorUndo};
+
 
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
 struct ChunkData {
+    /// Client-chosen stable id, e.g. `doc-42#p3`. Inserting a chunk whose
+    /// `external_id` already exists overwrites that chunk instead of adding a
+    /// duplicate, so retried batches are idempotent.
+    #[serde(default)]
+    external_id: Option<String>,
     embedding: Vec<f32>,
     text: String,
-    metadata: String,
+    #[serde(default)]
+    metadata: Option<Metadata>,
+}
+
+/// Chunk metadata, a JSON object that search filters can match on.
+type Metadata = serde_json::Map<String, Value>;
+
+/// Parses stored metadata. Rows written before metadata had to be a JSON
+/// object come back as a plain string.
+fn parse_metadata(metadata: Option<String>) -> Option<Value> {
+    metadata.map(|text| serde_json::from_str(&text).unwrap_or(Value::String(text)))
+}
+
+fn serialize_metadata(metadata: Option<Metadata>) -> Option<String> {
+    metadata.map(|metadata| Value::Object(metadata).to_string())
+}
+
+/// Encodes an embedding for the `embedding` column as little-endian `f32`s.
+fn embedding_to_blob(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+/// Decodes an `embedding` column value, `None` if it does not hold
+/// `dimensions` values.
+fn embedding_from_blob(blob: &[u8], dimensions: usize) -> Option<Vec<f32>> {
+    if blob.len() != dimensions * 4 {
+        return None;
+    }
+    Some(blob.chunks_exact(4).map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap())).collect())
+}
+
+/// Distance metric used by a database's vector index.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, JsonSchema, ApiComponent)]
+#[serde(rename_all = "snake_case")]
+enum Metric {
+    #[default]
+    Cosine,
+    L2,
+    InnerProduct,
+    Hamming,
+    Jaccard,
+}
+
+impl Metric {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Metric::Cosine => "cosine",
+            Metric::L2 => "l2",
+            Metric::InnerProduct => "inner_product",
+            Metric::Hamming => "hamming",
+            Metric::Jaccard => "jaccard",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "cosine" => Some(Metric::Cosine),
+            "l2" => Some(Metric::L2),
+            "inner_product" => Some(Metric::InnerProduct),
+            "hamming" => Some(Metric::Hamming),
+            "jaccard" => Some(Metric::Jaccard),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> MetricKind {
+        match self {
+            Metric::Cosine => MetricKind::Cos,
+            Metric::L2 => MetricKind::L2sq,
+            Metric::InnerProduct => MetricKind::IP,
+            Metric::Hamming => MetricKind::Hamming,
+            Metric::Jaccard => MetricKind::Tanimoto,
+        }
+    }
+
+    /// Bit-level metrics only work on binary vectors.
+    fn is_binary(&self) -> bool {
+        matches!(self, Metric::Hamming | Metric::Jaccard)
+    }
+
+    /// Distance between two vectors as usearch measures it. Binary metrics
+    /// treat positive components as set bits.
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        let dot = || a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+        let bits = || a.iter().zip(b).map(|(x, y)| (*x > 0.0, *y > 0.0));
+        match self {
+            Metric::Cosine => {
+                let norms = (a.iter().map(|x| x * x).sum::<f32>() * b.iter().map(|y| y * y).sum::<f32>()).sqrt();
+                if norms == 0.0 { 1.0 } else { 1.0 - dot() / norms }
+            }
+            Metric::InnerProduct => 1.0 - dot(),
+            Metric::L2 => a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum(),
+            Metric::Hamming => bits().filter(|(x, y)| x != y).count() as f32,
+            Metric::Jaccard => {
+                let union = bits().filter(|(x, y)| *x || *y).count();
+                let intersection = bits().filter(|(x, y)| *x && *y).count();
+                if union == 0 { 0.0 } else { 1.0 - intersection as f32 / union as f32 }
+            }
+        }
+    }
+
+    /// Turns a usearch distance into a score where higher means more similar.
+    fn score(&self, distance: f32) -> f32 {
+        match self {
+            Metric::Cosine | Metric::InnerProduct | Metric::Jaccard => 1.0 - distance,
+            Metric::L2 | Metric::Hamming => -distance,
+        }
+    }
+}
+
+/// Scalar type vectors are stored as in a database's vector index.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema, ApiComponent)]
+#[serde(rename_all = "snake_case")]
+enum Quantization {
+    F32,
+    F16,
+    I8,
+    B1,
+}
+
+impl Quantization {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Quantization::F32 => "f32",
+            Quantization::F16 => "f16",
+            Quantization::I8 => "i8",
+            Quantization::B1 => "b1",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "f32" => Some(Quantization::F32),
+            "f16" => Some(Quantization::F16),
+            "i8" => Some(Quantization::I8),
+            "b1" => Some(Quantization::B1),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> ScalarKind {
+        match self {
+            Quantization::F32 => ScalarKind::F32,
+            Quantization::F16 => ScalarKind::F16,
+            Quantization::I8 => ScalarKind::I8,
+            Quantization::B1 => ScalarKind::B1,
+        }
+    }
+
+    /// Binary vectors for bit-level metrics, full precision otherwise.
+    fn default_for(metric: Metric) -> Self {
+        if metric.is_binary() { Quantization::B1 } else { Quantization::F32 }
+    }
+}
+
+impl FromSql for Metric {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let value = value.as_str()?;
+        Metric::parse(value).ok_or_else(|| FromSqlError::Other(format!("unknown metric {:?}", value).into()))
+    }
+}
+
+impl FromSql for Quantization {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let value = value.as_str()?;
+        Quantization::parse(value).ok_or_else(|| FromSqlError::Other(format!("unknown quantization {:?}", value).into()))
+    }
+}
+
+/// Index settings of a database. Unset fields fall back to defaults.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, JsonSchema, ApiComponent)]
+struct DatabaseOptions {
+    metric: Option<Metric>,
+    /// Defaults to `b1` for `hamming` and `jaccard`, `f32` otherwise.
+    quantization: Option<Quantization>,
+    /// HNSW graph connectivity; usearch picks a default when unset.
+    connectivity: Option<usize>,
+    /// Candidates explored while inserting; usearch picks a default when unset.
+    expansion_add: Option<usize>,
+    /// Candidates explored while searching; usearch picks a default when unset.
+    expansion_search: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct CreateDatabaseRequest {
+    database_id: DatabaseId,
+    dimensions: usize,
+    #[serde(flatten)]
+    options: DatabaseOptions,
+    description: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
 struct InsertChunkRequest {
-    database_id: String,
+    database_id: DatabaseId,
     chunks: Vec<ChunkData>,
 }
 
+/// Deepest hit a search can reach, counting the hits skipped by its cursor.
+const MAX_SEARCH_DEPTH: usize = 10_000;
+
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
 struct SearchRequest {
-    database_id: String,
+    database_id: DatabaseId,
     embeddings: Vec<Vec<f32>>,
     num_results: usize,
+    /// Overrides the database's `expansion_search` for this request.
+    #[serde(default)]
+    expansion_search: Option<usize>,
+    /// Only return chunks whose metadata matches. The filter is applied while
+    /// traversing the index, so up to `num_results` matching chunks are found.
+    #[serde(default)]
+    filter: Option<Filter>,
+    /// Keywords for hybrid search. Chunks whose text contains any of them are
+    /// ranked by BM25, and that ranking is fused with the vector ranking.
+    #[serde(default)]
+    text_query: Option<String>,
+    /// How the rankings of a hybrid search are fused; defaults to reciprocal
+    /// rank fusion with `k` = 60.
+    #[serde(default)]
+    fusion: Fusion,
+    /// Drops hits scoring below this, so that poor matches give an empty
+    /// answer. Scores are oriented as in `SearchResult::score`, e.g. `0.8`
+    /// keeps cosine similarities of at least 0.8.
+    #[serde(default)]
+    min_score: Option<f32>,
+    /// Drops hits farther than this from the query, in the database metric:
+    /// `1 - similarity` for cosine, inner product and Jaccard, the squared
+    /// Euclidean distance for L2 and the number of differing bits for Hamming.
+    #[serde(default)]
+    max_distance: Option<f32>,
+    /// Also return the vector stored in the index for each hit. It is
+    /// reconstructed from the index, so quantized databases return
+    /// approximate values.
+    #[serde(default)]
+    include_embedding: bool,
+    /// Return the text of each hit. Disable along with `include_metadata` to
+    /// get ids and scores without reading the chunk table.
+    #[serde(default = "default_true")]
+    include_text: bool,
+    /// Return the metadata of each hit.
+    #[serde(default = "default_true")]
+    include_metadata: bool,
+    /// Re-ranks the hits to diversify them, using the stored vectors.
+    #[serde(default)]
+    mmr: Option<Mmr>,
+    /// Returns up to `num_results` groups of hits sharing a metadata value
+    /// instead of individual hits.
+    #[serde(default)]
+    group_by: Option<GroupBy>,
+    /// `next_cursor` of a previous response, to fetch the following
+    /// `num_results` hits of the same search.
+    #[serde(default)]
+    cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct SearchResponse {
+    /// Hits for each query embedding, best first. Absent when grouping.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    results: Option<Vec<Vec<SearchResult>>>,
+    /// Groups for each query embedding, ordered by their best hit. Only
+    /// present when grouping.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    groups: Option<Vec<Vec<SearchGroup>>>,
+    /// Passed as `cursor` to fetch the next page; null on the last page. A
+    /// cursor is rejected with 409 once the database has been written to.
+    next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct SearchGroup {
+    /// Value of the grouped field shared by the hits.
+    value: Value,
+    /// Best hits of the group, best first.
+    hits: Vec<SearchResult>,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
 struct SearchResult {
-    text: String,
-    metadata: Option<String>,
+    chunk_id: i64,
+    /// Omitted when the request excludes text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    /// Omitted when the request excludes metadata, null when the chunk has none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<Value>,
+    /// Similarity to the query, higher is better whatever the database metric:
+    /// `1 - distance` for cosine, inner product and Jaccard, and the negated
+    /// distance for L2 (squared Euclidean) and Hamming. Hybrid searches
+    /// return the fused score instead, and full-text searches the negated BM25.
     score: f32,
+    /// Set when the request includes embeddings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    embedding: Option<Vec<f32>>,
+    /// Excerpt of the text with the matched terms highlighted, set by
+    /// full-text search.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    snippet: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct TextSearchRequest {
+    database_id: DatabaseId,
+    /// FTS5 query over the chunk text, supporting phrases (`"exact words"`),
+    /// prefixes (`pref*`) and boolean operators (`red AND (car OR bike) NOT truck`).
+    query: String,
+    num_results: usize,
+    /// Only return chunks whose metadata matches.
+    #[serde(default)]
+    filter: Option<Filter>,
+    #[serde(default)]
+    snippet: SnippetOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct GetChunkQuery {
+    /// Also return the vector stored in the index. It is reconstructed from
+    /// the index, so quantized databases return approximate values.
+    #[serde(default)]
+    include_embedding: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct GetChunksRequest {
+    chunk_ids: Vec<i64>,
+    /// See `GetChunkQuery::include_embedding`.
+    #[serde(default)]
+    include_embedding: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct Chunk {
+    chunk_id: i64,
+    external_id: Option<String>,
+    text: String,
+    metadata: Option<Value>,
+    /// Incremented on every update, see `UpdateChunkRequest::version`.
+    version: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    embedding: Option<Vec<f32>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct GetChunksResponse {
+    /// Found chunks, in request order.
+    chunks: Vec<Chunk>,
+    missing_ids: Vec<i64>,
+}
+
+/// Fields left unset keep their current value.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct UpdateChunkRequest {
+    text: Option<String>,
+    metadata: Option<Metadata>,
+    /// Replaces the chunk's vector in the index.
+    embedding: Option<Vec<f32>>,
+    /// Version the update is based on. When set, the update fails with 409 if
+    /// the chunk has been modified since.
+    version: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct DeleteChunksRequest {
+    chunk_ids: Vec<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
 struct DropTableRequest {
-    database_id: String,
+    database_id: DatabaseId,
+}
+
+/// Settings persisted for a database in the `databases` table. Zero HNSW
+/// parameters leave the choice to usearch.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct DatabaseSettings {
+    dimensions: usize,
+    metric: Metric,
+    quantization: Quantization,
+    connectivity: usize,
+    expansion_add: usize,
+    expansion_search: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct DatabaseInfo {
+    database_id: DatabaseId,
+    #[serde(flatten)]
+    settings: DatabaseSettings,
+    description: Option<String>,
+    /// Number of vectors in the usearch index.
+    index_size: usize,
+    /// Number of rows in the chunk table.
+    chunk_count: u64,
+    /// Size of the `.usearch` file in bytes.
+    index_file_size: u64,
+    /// Memory used by the loaded index in bytes, 0 while it is not loaded.
+    memory_usage: usize,
+    /// Unix timestamp in seconds.
+    created_at: i64,
+    /// Unix timestamp in seconds of the last write.
+    updated_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct VerifyQuery {
+    /// Fix the discrepancies found instead of only reporting them.
+    #[serde(default)]
+    repair: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, ApiComponent)]
+struct VerifyReport {
+    database_id: DatabaseId,
+    /// Whether the `.usearch` file exists.
+    index_file_exists: bool,
+    /// Number of rows in the chunk table.
+    chunk_count: u64,
+    /// Number of vectors in the usearch index.
+    index_size: usize,
+    /// Discrepancies found, before any repair.
+    #[serde(flatten)]
+    discrepancies: Discrepancies,
+    /// Whether a repair was run, which only happens if one was requested and
+    /// something was found.
+    repaired: bool,
+    /// Whether rows and index agree, after the repair if one was run.
+    consistent: bool,
 }
 
 struct AppState {
     db_pool: Pool,
+    indexes: IndexRegistry,
+    jobs: JobRegistry,
+}
+
+async fn ensure_databases_table(db_pool: &Pool) -> Result<(), async_sqlite::Error> {
+    db_pool.conn(|conn| {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS databases (
+                database_id TEXT PRIMARY KEY,
+                dimensions INTEGER NOT NULL,
+                metric TEXT NOT NULL,
+                quantization TEXT NOT NULL,
+                connectivity INTEGER NOT NULL,
+                expansion_add INTEGER NOT NULL,
+                expansion_search INTEGER NOT NULL,
+                description TEXT,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL,
+                revision INTEGER NOT NULL DEFAULT 0,
+                flushed_revision INTEGER NOT NULL DEFAULT 0,
+                generation INTEGER NOT NULL DEFAULT 0,
+                index_size INTEGER NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+        // Tables created before revisions were tracked lack the column.
+        if conn.prepare("SELECT revision FROM databases LIMIT 0").is_err() {
+            conn.execute("ALTER TABLE databases ADD COLUMN revision INTEGER NOT NULL DEFAULT 0", [])?;
+        }
+        // Indexes used to be saved on every write, so theirs are up to date.
+        if conn.prepare("SELECT flushed_revision FROM databases LIMIT 0").is_err() {
+            conn.execute("ALTER TABLE databases ADD COLUMN flushed_revision INTEGER NOT NULL DEFAULT 0", [])?;
+            conn.execute("UPDATE databases SET flushed_revision = revision", [])?;
+        }
+        if conn.prepare("SELECT generation FROM databases LIMIT 0").is_err() {
+            conn.execute("ALTER TABLE databases ADD COLUMN generation INTEGER NOT NULL DEFAULT 0", [])?;
+            conn.execute("UPDATE databases SET generation = random()", [])?;
+        }
+        // Nothing else touches the index files during startup, so they can be
+        // viewed to count their vectors.
+        if conn.prepare("SELECT index_size FROM databases LIMIT 0").is_err() {
+            conn.execute("ALTER TABLE databases ADD COLUMN index_size INTEGER NOT NULL DEFAULT 0", [])?;
+            let mut statement = conn.prepare(&format!("SELECT database_id, {} FROM databases", SETTINGS_COLUMNS))?;
+            let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, settings_from_row(row, 1)?)))?;
+            for row in rows.collect::<Result<Vec<_>, _>>()? {
+                let (database_id, settings) = row;
+                let index_size = create_index(&settings)
+                    .ok()
+                    .filter(|index| index.view(&format!("{}.usearch", database_id)).is_ok())
+                    .map_or(0, |index| index.size());
+                conn.execute(
+                    "UPDATE databases SET index_size = ? WHERE database_id = ?",
+                    params![index_size as i64, database_id],
+                )?;
+            }
+        }
+        Ok(())
+    }).await
+}
+
+/// Lowercases ids recorded before ids were case-folded, renaming their index
+/// files along. An id whose lowercase form is taken already shares its chunk
+/// table with that database, so it is left for an operator to separate.
+async fn lowercase_database_ids(db_pool: &Pool) -> Result<(), async_sqlite::Error> {
+    db_pool.conn(|conn| {
+        let mut statement = conn.prepare("SELECT database_id FROM databases WHERE database_id != lower(database_id)")?;
+        let ids = statement.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+        for id in ids {
+            let lower = id.to_ascii_lowercase();
+            let taken = conn
+                .query_row("SELECT 1 FROM databases WHERE database_id = ?", [&lower], |_| Ok(()))
+                .optional()?
+                .is_some();
+            if taken {
+                warn!("Database {} shares its chunk table with {} and is unreachable until they are separated", id, lower);
+                continue;
+            }
+            let (from, to) = (format!("{}.usearch", id), format!("{}.usearch", lower));
+            if std::path::Path::new(&from).exists() {
+                if let Err(error) = std::fs::rename(&from, &to) {
+                    warn!("Failed to rename {} to {}: {}", from, to, error);
+                    continue;
+                }
+            }
+            conn.execute("UPDATE databases SET database_id = ? WHERE database_id = ?", [&lower, &id])?;
+        }
+        Ok(())
+    }).await
+}
+
+/// Settings every index had before databases recorded their own.
+fn legacy_settings() -> DatabaseSettings {
+    DatabaseSettings {
+        dimensions: 2,
+        metric: Metric::InnerProduct,
+        quantization: Quantization::F32,
+        connectivity: 0,
+        expansion_add: 0,
+        expansion_search: 0,
+    }
+}
+
+/// Registers chunk tables created before the `databases` table existed, so
+/// their data stays reachable. Their indexes were all built with
+/// `legacy_settings`; an index file that does not match is left alone.
+async fn adopt_legacy_databases(db_pool: &Pool) -> Result<(), async_sqlite::Error> {
+    db_pool.conn(|conn| {
+        let mut statement = conn.prepare(
+            r"SELECT substr(name, 8) FROM sqlite_master
+              WHERE type = 'table' AND name LIKE 'chunks\_%' ESCAPE '\' AND instr(name, ':') = 0
+                AND lower(substr(name, 8)) NOT IN (SELECT database_id FROM databases)",
+        )?;
+        let ids = statement.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+        let settings = legacy_settings();
+        for id in ids {
+            let database_id = match id.parse::<DatabaseId>() {
+                Ok(database_id) => database_id,
+                Err(error) => {
+                    warn!("Skipping legacy chunk table: {}", error);
+                    continue;
+                }
+            };
+
+            let (legacy_file, index_file) = (format!("{}.usearch", id), database_id.index_file());
+            let mut index_size = 0;
+            if std::path::Path::new(&legacy_file).exists() {
+                let viewed = create_index(&settings)
+                    .ok()
+                    .filter(|index| index.view(&legacy_file).is_ok() && index.dimensions() == settings.dimensions);
+                let Some(index) = viewed else {
+                    warn!("Not adopting database {}: {} was not built with the legacy settings", database_id, legacy_file);
+                    continue;
+                };
+                index_size = index.size();
+                if legacy_file != index_file {
+                    if let Err(error) = std::fs::rename(&legacy_file, &index_file) {
+                        warn!("Not adopting database {}: failed to rename {}: {}", database_id, legacy_file, error);
+                        continue;
+                    }
+                }
+            }
+
+            conn.execute(
+                "INSERT INTO databases (
+                    database_id, dimensions, metric, quantization, connectivity, expansion_add, expansion_search,
+                    created_at, updated_at, generation, index_size
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, random(), ?)",
+                params![
+                    database_id.as_str(),
+                    settings.dimensions as i64,
+                    settings.metric.as_str(),
+                    settings.quantization.as_str(),
+                    settings.connectivity as i64,
+                    settings.expansion_add as i64,
+                    settings.expansion_search as i64,
+                    unix_timestamp(),
+                    unix_timestamp(),
+                    index_size as i64,
+                ],
+            )?;
+            info!("Adopted legacy database {}", database_id);
+        }
+        Ok(())
+    }).await
+}
+
+/// Brings chunk tables created by earlier versions up to date: adds the
+/// `version` and `external_id` columns, the full-text index and the
+/// `embedding` column. Existing
+/// rows start at version 1, and rows stored before embeddings were kept have
+/// none until the index is rebuilt.
+async fn upgrade_chunk_tables(db_pool: &Pool) -> Result<(), async_sqlite::Error> {
+    db_pool.conn(|conn| {
+        let mut statement = conn.prepare("SELECT database_id FROM databases")?;
+        let ids = statement.query_map([], |row| row.get::<_, String>(0))?;
+        for id in ids {
+            let database_id = match id?.parse::<DatabaseId>() {
+                Ok(database_id) => database_id,
+                Err(error) => {
+                    warn!("Skipping chunk table upgrade: {}", error);
+                    continue;
+                }
+            };
+            let table_name = database_id.table_name();
+            if conn.prepare(&format!("SELECT version FROM {} LIMIT 0", table_name)).is_err() {
+                conn.execute(&format!("ALTER TABLE {} ADD COLUMN version INTEGER NOT NULL DEFAULT 1", table_name), [])?;
+            }
+            // A column added later cannot be declared UNIQUE, so an index
+            // enforces it instead; upserts on `external_id` work with either.
+            if conn.prepare(&format!("SELECT external_id FROM {} LIMIT 0", table_name)).is_err() {
+                conn.execute_batch(&format!(
+                    r#"ALTER TABLE {table} ADD COLUMN external_id TEXT;
+                       CREATE UNIQUE INDEX "{table}:external_id" ON {table} (external_id);"#,
+                    table = table_name,
+                ))?;
+            }
+            full_text::create_text_index(conn, &database_id)?;
+            if conn.prepare(&format!("SELECT embedding FROM {} LIMIT 0", table_name)).is_err() {
+                conn.execute(&format!("ALTER TABLE {} ADD COLUMN embedding BLOB", table_name), [])?;
+            }
+        }
+        Ok(())
+    }).await
+}
+
+const SETTINGS_COLUMNS: &str = "dimensions, metric, quantization, connectivity, expansion_add, expansion_search";
+
+/// Reads the columns listed in `SETTINGS_COLUMNS`, starting at `offset`.
+fn settings_from_row(row: &Row, offset: usize) -> rusqlite::Result<DatabaseSettings> {
+    Ok(DatabaseSettings {
+        dimensions: row.get::<_, i64>(offset)? as usize,
+        metric: row.get(offset + 1)?,
+        quantization: row.get(offset + 2)?,
+        connectivity: row.get::<_, i64>(offset + 3)? as usize,
+        expansion_add: row.get::<_, i64>(offset + 4)? as usize,
+        expansion_search: row.get::<_, i64>(offset + 5)? as usize,
+    })
+}
+
+async fn load_settings(db_pool: &Pool, database_id: &DatabaseId) -> Result<Option<DatabaseSettings>, actix_web::Error> {
+    let database_id = database_id.to_string();
+    db_pool.conn(move |conn| {
+        conn.query_row(
+            &format!("SELECT {} FROM databases WHERE database_id = ?", SETTINGS_COLUMNS),
+            [&database_id],
+            |row| settings_from_row(row, 0),
+        ).optional()
+    }).await.map_err(actix_web::error::ErrorInternalServerError)
+}
+
+fn unix_timestamp() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs() as i64).unwrap_or(0)
+}
+
+/// Bumps the `updated_at` timestamp and the revision of a database after a
+/// write. Writers call it under the index write lock, so a revision read
+/// under the read lock matches the index.
+async fn touch_database(db_pool: &Pool, database_id: &DatabaseId) -> Result<(), actix_web::Error> {
+    let database_id = database_id.to_string();
+    db_pool.conn(move |conn| {
+        conn.execute(
+            "UPDATE databases SET updated_at = ?, revision = revision + 1 WHERE database_id = ?",
+            params![unix_timestamp(), database_id],
+        )
+    }).await.map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(())
+}
+
+/// Returns the generation of a database, a random number drawn anew when the
+/// database is created and when its index is replaced, or `None` if it does
+/// not exist.
+async fn load_generation(db_pool: &Pool, database_id: &DatabaseId) -> Result<Option<i64>, actix_web::Error> {
+    let database_id = database_id.to_string();
+    db_pool.conn(move |conn| {
+        conn.query_row("SELECT generation FROM databases WHERE database_id = ?", [&database_id], |row| row.get(0))
+            .optional()
+    }).await.map_err(actix_web::error::ErrorInternalServerError)
+}
+
+/// Returns the revision of a database, which changes with every write.
+async fn load_revision(db_pool: &Pool, database_id: &DatabaseId) -> Result<u64, actix_web::Error> {
+    let database_id = database_id.to_string();
+    db_pool.conn(move |conn| {
+        conn.query_row("SELECT revision FROM databases WHERE database_id = ?", [&database_id], |row| row.get::<_, i64>(0))
+    }).await.map(|revision| revision as u64).map_err(actix_web::error::ErrorInternalServerError)
+}
+
+/// Loads the settings of a database, failing with 404 if it was never created.
+async fn require_settings(db_pool: &Pool, database_id: &DatabaseId) -> Result<DatabaseSettings, actix_web::Error> {
+    load_settings(db_pool, database_id).await?
+        .ok_or_else(|| actix_web::error::ErrorNotFound(format!("database {} does not exist", database_id)))
+}
+
+/// Records a new database and creates its chunk table. Returns `false` if the
+/// database already exists, or a chunk table or index file is left under its
+/// id, which the new database would otherwise silently inherit.
+async fn insert_database(
+    db_pool: &Pool,
+    database_id: &DatabaseId,
+    settings: DatabaseSettings,
+    description: Option<String>,
+) -> Result<bool, actix_web::Error> {
+    let id = database_id.to_string();
+    let table_name = database_id.table_name();
+    let database_id = database_id.clone();
+    db_pool.conn_mut(move |conn| {
+        let tx = conn.transaction()?;
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO databases (
+                database_id, dimensions, metric, quantization, connectivity, expansion_add, expansion_search,
+                description, created_at, updated_at, generation
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, random())",
+            params![
+                id,
+                settings.dimensions as i64,
+                settings.metric.as_str(),
+                settings.quantization.as_str(),
+                settings.connectivity as i64,
+                settings.expansion_add as i64,
+                settings.expansion_search as i64,
+                description,
+                unix_timestamp(),
+                unix_timestamp(),
+            ],
+        )?;
+        let leftover = tx
+            .query_row("SELECT 1 FROM sqlite_master WHERE name = ?", [&table_name], |_| Ok(()))
+            .optional()?
+            .is_some()
+            || std::path::Path::new(&database_id.index_file()).exists();
+        if inserted == 0 || leftover {
+            return Ok(false);
+        }
+        tx.execute(
+            &format!("CREATE TABLE {} (
+                chunk_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                external_id TEXT UNIQUE,
+                text TEXT,
+                metadata TEXT,
+                version INTEGER NOT NULL DEFAULT 1,
+                embedding BLOB
+            )", table_name),
+            [],
+        )?;
+        full_text::create_text_index(&tx, &database_id)?;
+        tx.commit()?;
+        Ok(true)
+    }).await.map_err(actix_web::error::ErrorInternalServerError)
+}
+
+/// Resolves creation options into settings, rejecting invalid combinations.
+fn settings_from_options(options: &DatabaseOptions, dimensions: usize) -> Result<DatabaseSettings, actix_web::Error> {
+    if dimensions == 0 {
+        return Err(actix_web::error::ErrorBadRequest("dimensions must be greater than zero"));
+    }
+    let metric = options.metric.unwrap_or_default();
+    let quantization = options.quantization.unwrap_or_else(|| Quantization::default_for(metric));
+    if metric.is_binary() != (quantization == Quantization::B1) {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "metric {} cannot be used with {} quantization",
+            metric.as_str(),
+            quantization.as_str()
+        )));
+    }
+    Ok(DatabaseSettings {
+        dimensions,
+        metric,
+        quantization,
+        connectivity: options.connectivity.unwrap_or(0),
+        expansion_add: options.expansion_add.unwrap_or(0),
+        expansion_search: options.expansion_search.unwrap_or(0),
+    })
+}
+
+fn check_dimensions(settings: &DatabaseSettings, embedding: &[f32]) -> Result<(), actix_web::Error> {
+    if embedding.len() != settings.dimensions {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "embedding has {} dimensions but the database expects {}",
+            embedding.len(),
+            settings.dimensions
+        )));
+    }
+    Ok(())
+}
+
+/// Creates an empty index with a database's settings.
+fn create_index(settings: &DatabaseSettings) -> Result<Index, actix_web::Error> {
+    let options = IndexOptions {
+        dimensions: settings.dimensions,
+        metric: settings.metric.kind(),
+        quantization: settings.quantization.kind(),
+        connectivity: settings.connectivity,
+        expansion_add: settings.expansion_add,
+        expansion_search: settings.expansion_search,
+        multi: true,
+    };
+    new_index(&options).map_err(actix_web::error::ErrorInternalServerError)
+}
+
+fn load_or_create_index(database_id: &DatabaseId, settings: &DatabaseSettings) -> Result<Index, actix_web::Error> {
+    let index_file = database_id.index_file();
+    let index = create_index(settings)?;
+    
+    if std::path::Path::new(&index_file).exists() {
+        index.load(&index_file).map_err(actix_web::error::ErrorInternalServerError)?;
+    }
+
+    // Expansion factors are runtime parameters and are not restored by `load`.
+    if settings.expansion_add > 0 {
+        index.change_expansion_add(settings.expansion_add);
+    }
+    if settings.expansion_search > 0 {
+        index.change_expansion_search(settings.expansion_search);
+    }
+    
+    Ok(index)
+}
+
+/// Returns the resident index of a database, loading it on first use.
+fn open_index(app_state: &AppState, database_id: &DatabaseId, settings: &DatabaseSettings) -> Result<IndexHandle, actix_web::Error> {
+    app_state.indexes.get_or_open(database_id.as_str(), || load_or_create_index(database_id, settings))
+}
+
+/// Saves an index with unsaved changes and records the revision its file is
+/// at. The caller holds the index lock, so no write is half applied.
+async fn save_index(db_pool: &Pool, database_id: &DatabaseId, index: &SharedIndex) -> Result<(), actix_web::Error> {
+    if !index.take_dirty() {
+        return Ok(());
+    }
+    // Save beside the file and rename over it, so a crash mid-save leaves
+    // the previous file whole.
+    let index_file = database_id.index_file();
+    let staging_file = format!("{}.saving", index_file);
+    let saved = index.save(&staging_file)
+        .map_err(actix_web::error::ErrorInternalServerError)
+        .and_then(|()| std::fs::rename(&staging_file, &index_file).map_err(actix_web::error::ErrorInternalServerError));
+    if let Err(error) = saved {
+        let _ = std::fs::remove_file(&staging_file);
+        index.mark_dirty();
+        return Err(error);
+    }
+    let (database_id, index_size) = (database_id.to_string(), index.size() as i64);
+    db_pool.conn(move |conn| {
+        conn.execute(
+            "UPDATE databases SET flushed_revision = revision, index_size = ? WHERE database_id = ?",
+            params![index_size, database_id],
+        )
+    }).await.map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(())
+}
+
+/// Saves every resident index with unsaved changes, then unloads those over
+/// the memory budget that could not be while they were dirty.
+async fn flush_indexes(app_state: &AppState) {
+    for (database_id, handle) in app_state.indexes.resident() {
+        let Ok(database_id) = database_id.parse::<DatabaseId>() else {
+            continue;
+        };
+        let index = handle.read().await;
+        if let Err(error) = save_index(&app_state.db_pool, &database_id, &index).await {
+            warn!("Failed to save index {}: {}", database_id, error);
+        }
+    }
+    app_state.indexes.evict_over_budget();
+}
+
+/// Packs a vector into bytes for a binary index, most significant bit first,
+/// with positive components as set bits.
+fn pack_bits(embedding: &[f32]) -> Vec<b1x8> {
+    let bytes: Vec<u8> = embedding.chunks(8)
+        .map(|bits| bits.iter().enumerate().fold(0, |byte, (bit, x)| byte | (((*x > 0.0) as u8) << (7 - bit))))
+        .collect();
+    b1x8::from_u8s(&bytes).to_vec()
+}
+
+/// Searches the index for each embedding, only visiting `allowed` keys if set.
+fn search_index(
+    index: &Index,
+    settings: &DatabaseSettings,
+    embeddings: &[Vec<f32>],
+    count: usize,
+    allowed: Option<&HashSet<u64>>,
+) -> Result<Vec<Matches>, actix_web::Error> {
+    embeddings.iter()
+        .map(|embedding| {
+            // usearch adds `f32` vectors to binary indexes correctly but
+            // garbles `f32` queries, so those are packed here.
+            match (allowed, settings.quantization == Quantization::B1) {
+                (Some(allowed), false) => index.filtered_search(embedding, count, |key| allowed.contains(&key)),
+                (Some(allowed), true) => index.filtered_search(&pack_bits(embedding), count, |key| allowed.contains(&key)),
+                (None, false) => index.search(embedding, count),
+                (None, true) => index.search(&pack_bits(embedding), count),
+            }.map_err(actix_web::error::ErrorInternalServerError)
+        })
+        .collect()
+}
+
+/// Ranks chunks matching the FTS5 query `query` by BM25, returning up to
+/// `limit` `(chunk_id, score)` pairs, best first. Scores are negated BM25 so
+/// that higher is better.
+async fn keyword_search(
+    db_pool: &Pool,
+    database_id: &DatabaseId,
+    query: String,
+    filter: Option<&Filter>,
+    limit: usize,
+) -> Result<Vec<(u64, f32)>, actix_web::Error> {
+    let mut params = vec![SqlValue::Text(query)];
+    let condition = match filter {
+        Some(filter) => filter.to_sql(&mut params).map_err(actix_web::error::ErrorBadRequest)?,
+        None => "1".to_string(),
+    };
+    params.push(SqlValue::Integer(limit as i64));
+
+    let sql = format!(
+        "SELECT chunk_id, -bm25({fts}) FROM {fts} JOIN {table} ON chunk_id = {fts}.rowid
+         WHERE {fts} MATCH ? AND ({condition}) ORDER BY bm25({fts}) LIMIT ?",
+        fts = full_text::text_index_table(database_id),
+        table = database_id.table_name(),
+        condition = condition,
+    );
+    db_pool.conn(move |conn| {
+        let mut statement = conn.prepare(&sql)?;
+        let hits = statement.query_map(params_from_iter(params), |row| {
+            Ok((row.get::<_, i64>(0)? as u64, row.get::<_, f64>(1)? as f32))
+        })?;
+        hits.collect()
+    }).await.map_err(actix_web::error::ErrorInternalServerError)
+}
+
+/// Returns the ids of the chunks whose metadata matches `filter`.
+async fn matching_chunk_ids(db_pool: &Pool, database_id: &DatabaseId, filter: &Filter) -> Result<HashSet<u64>, actix_web::Error> {
+    let mut params = Vec::new();
+    let condition = filter.to_sql(&mut params).map_err(actix_web::error::ErrorBadRequest)?;
+    let table_name = database_id.table_name();
+    db_pool.conn(move |conn| {
+        let mut statement = conn.prepare(&format!("SELECT chunk_id FROM {} WHERE {}", table_name, condition))?;
+        let ids = statement.query_map(params_from_iter(params), |row| row.get::<_, i64>(0).map(|id| id as u64))?;
+        ids.collect()
+    }).await.map_err(actix_web::error::ErrorInternalServerError)
+}
+
+#[api_operation(summary = "Create a database")]
+async fn create_database(
+    app_state: web::Data<Arc<AppState>>,
+    request: web::Json<CreateDatabaseRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let request = request.into_inner();
+    let settings = settings_from_options(&request.options, request.dimensions)?;
+
+    if !insert_database(&app_state.db_pool, &request.database_id, settings, request.description).await? {
+        return Err(actix_web::error::ErrorConflict(format!(
+            "database {} already exists or left a chunk table or index file behind",
+            request.database_id
+        )));
+    }
+
+    Ok(HttpResponse::Created().json(json!({"status": "success", "message": "Database created successfully"})))
+}
+
+/// A row of the `databases` table.
+struct DatabaseRecord {
+    database_id: DatabaseId,
+    settings: DatabaseSettings,
+    description: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+    /// Vectors in the index file when it was last saved.
+    index_size: usize,
+}
+
+/// Gathers the settings and current statistics of a database. Indexes that
+/// are not loaded are described from their last save rather than loaded, so
+/// listing databases does not pull them all into memory.
+async fn describe(app_state: &AppState, record: DatabaseRecord) -> Result<DatabaseInfo, actix_web::Error> {
+    let DatabaseRecord { database_id, settings, description, created_at, updated_at, index_size } = record;
+    let (index_size, memory_usage) = match app_state.indexes.get(database_id.as_str()) {
+        Some(handle) => {
+            let index = handle.read().await;
+            (index.size(), index.memory_usage())
+        }
+        None => (index_size, 0),
+    };
+
+    let table_name = database_id.table_name();
+    let chunk_count: i64 = app_state.db_pool.conn(move |conn| {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {}", table_name), [], |row| row.get(0))
+    }).await.map_err(actix_web::error::ErrorInternalServerError)?;
+
+    let index_file_size = std::fs::metadata(database_id.index_file()).map(|metadata| metadata.len()).unwrap_or(0);
+
+    Ok(DatabaseInfo {
+        database_id,
+        settings,
+        description,
+        index_size,
+        chunk_count: chunk_count as u64,
+        index_file_size,
+        memory_usage,
+        created_at,
+        updated_at,
+    })
+}
+
+/// Loads `databases` rows, all of them or only `database_id`'s.
+async fn load_databases(db_pool: &Pool, database_id: Option<DatabaseId>) -> Result<Vec<DatabaseRecord>, actix_web::Error> {
+    let database_id = database_id.map(String::from);
+    db_pool.conn(move |conn| {
+        let mut statement = conn.prepare(&format!(
+            "SELECT database_id, {}, description, created_at, updated_at, index_size FROM databases
+             WHERE ?1 IS NULL OR database_id = ?1 ORDER BY database_id",
+            SETTINGS_COLUMNS
+        ))?;
+        let rows = statement.query_map([&database_id], |row| {
+            Ok(DatabaseRecord {
+                database_id: row.get::<_, String>(0)?.parse().map_err(|error| {
+                    rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(error))
+                })?,
+                settings: settings_from_row(row, 1)?,
+                description: row.get(7)?,
+                created_at: row.get(8)?,
+                updated_at: row.get(9)?,
+                index_size: row.get::<_, i64>(10)? as usize,
+            })
+        })?;
+        rows.collect()
+    }).await.map_err(actix_web::error::ErrorInternalServerError)
+}
+
+#[api_operation(summary = "List databases")]
+async fn list_databases(
+    app_state: web::Data<Arc<AppState>>,
+) -> actix_web::Result<HttpResponse> {
+    let mut databases = Vec::new();
+    for record in load_databases(&app_state.db_pool, None).await? {
+        databases.push(describe(&app_state, record).await?);
+    }
+
+    Ok(HttpResponse::Ok().json(databases))
+}
+
+#[api_operation(summary = "Describe a database")]
+async fn describe_database(
+    app_state: web::Data<Arc<AppState>>,
+    database_id: web::Path<String>,
+) -> actix_web::Result<HttpResponse> {
+    let database_id: DatabaseId = database_id.parse().map_err(actix_web::error::ErrorBadRequest)?;
+    let Some(record) = load_databases(&app_state.db_pool, Some(database_id.clone())).await?.pop() else {
+        return Err(actix_web::error::ErrorNotFound(format!("database {} does not exist", database_id)));
+    };
+
+    Ok(HttpResponse::Ok().json(describe(&app_state, record).await?))
+}
+
+/// Loads the rows of `chunk_ids`, in one query unless there are more than
+/// SQLite binds at once. Missing ids are absent from the returned map.
+async fn fetch_chunks(
+    db_pool: &Pool,
+    database_id: &DatabaseId,
+    chunk_ids: Vec<i64>,
+) -> Result<HashMap<i64, Chunk>, actix_web::Error> {
+    let chunk_ids: Vec<i64> = chunk_ids.into_iter().collect::<HashSet<_>>().into_iter().collect();
+    let table_name = database_id.table_name();
+    db_pool.conn(move |conn| {
+        let mut chunks = HashMap::new();
+        for batch in chunk_ids.chunks(MAX_SQL_PARAMS) {
+            let placeholders = vec!["?"; batch.len()].join(", ");
+            let mut statement = conn.prepare(&format!(
+                "SELECT chunk_id, external_id, text, metadata, version FROM {} WHERE chunk_id IN ({})",
+                table_name, placeholders
+            ))?;
+            let rows = statement.query_map(params_from_iter(batch), |row| {
+                Ok(Chunk {
+                    chunk_id: row.get(0)?,
+                    external_id: row.get(1)?,
+                    text: row.get(2)?,
+                    metadata: parse_metadata(row.get(3)?),
+                    version: row.get(4)?,
+                    embedding: None,
+                })
+            })?;
+            for chunk in rows {
+                let chunk = chunk?;
+                chunks.insert(chunk.chunk_id, chunk);
+            }
+        }
+        Ok(chunks)
+    }).await.map_err(actix_web::error::ErrorInternalServerError)
+}
+
+/// Reads a chunk's vector back from the index, `None` if it has none.
+fn reconstruct_embedding(index: &Index, chunk_id: i64) -> Result<Option<Vec<f32>>, actix_web::Error> {
+    let mut embedding = Vec::new();
+    let found = index.export(chunk_id as u64, &mut embedding).map_err(actix_web::error::ErrorInternalServerError)?;
+    // Keep the first vector if stale duplicates were ever stored under the key.
+    embedding.truncate(index.dimensions());
+    Ok((found > 0).then_some(embedding))
+}
+
+/// Fetches chunks in the order of `chunk_ids`, with their embeddings if asked.
+async fn get_chunks_in_order(
+    app_state: &AppState,
+    database_id: &DatabaseId,
+    chunk_ids: Vec<i64>,
+    include_embedding: bool,
+) -> Result<GetChunksResponse, actix_web::Error> {
+    let settings = require_settings(&app_state.db_pool, database_id).await?;
+    let mut rows = fetch_chunks(&app_state.db_pool, database_id, chunk_ids.clone()).await?;
+
+    if include_embedding {
+        let handle = open_index(app_state, database_id, &settings)?;
+        let index = handle.read().await;
+        for chunk in rows.values_mut() {
+            chunk.embedding = reconstruct_embedding(&index, chunk.chunk_id)?;
+        }
+    }
+
+    let mut response = GetChunksResponse { chunks: Vec::new(), missing_ids: Vec::new() };
+    for chunk_id in chunk_ids {
+        match rows.get(&chunk_id).cloned() {
+            Some(chunk) => response.chunks.push(chunk),
+            None => response.missing_ids.push(chunk_id),
+        }
+    }
+    Ok(response)
+}
+
+#[api_operation(summary = "Get a chunk by id")]
+async fn get_chunk(
+    app_state: web::Data<Arc<AppState>>,
+    path: web::Path<(String, i64)>,
+    query: web::Query<GetChunkQuery>,
+) -> actix_web::Result<HttpResponse> {
+    let (database_id, chunk_id) = path.into_inner();
+    let database_id: DatabaseId = database_id.parse().map_err(actix_web::error::ErrorBadRequest)?;
+
+    let response = get_chunks_in_order(&app_state, &database_id, vec![chunk_id], query.include_embedding).await?;
+    match response.chunks.into_iter().next() {
+        Some(chunk) => Ok(HttpResponse::Ok().json(chunk)),
+        None => Err(actix_web::error::ErrorNotFound(format!("chunk {} does not exist", chunk_id))),
+    }
+}
+
+#[api_operation(summary = "Get several chunks by id")]
+async fn get_chunks(
+    app_state: web::Data<Arc<AppState>>,
+    database_id: web::Path<String>,
+    request: web::Json<GetChunksRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let database_id: DatabaseId = database_id.parse().map_err(actix_web::error::ErrorBadRequest)?;
+    let request = request.into_inner();
+
+    let response = get_chunks_in_order(&app_state, &database_id, request.chunk_ids, request.include_embedding).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Removes the vectors of `chunk_ids` from the index and returns them so the
+/// removal can be undone. Nothing stays removed if any removal fails.
+fn remove_vectors(index: &Index, chunk_ids: &[i64]) -> Result<Vec<(i64, Vec<f32>)>, actix_web::Error> {
+    let mut removed = Vec::new();
+    for &chunk_id in chunk_ids {
+        let mut vectors = Vec::new();
+        let result = index.export(chunk_id as u64, &mut vectors)
+            .and_then(|found| if found > 0 { index.remove(chunk_id as u64) } else { Ok(0) });
+        match result {
+            Ok(0) => {}
+            Ok(_) => removed.push((chunk_id, vectors)),
+            Err(error) => {
+                restore_vectors(index, &removed);
+                return Err(actix_web::error::ErrorInternalServerError(error));
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// Puts back vectors taken out by `remove_vectors`.
+fn restore_vectors(index: &Index, removed: &[(i64, Vec<f32>)]) {
+    for (chunk_id, vectors) in removed {
+        for vector in vectors.chunks(index.dimensions()) {
+            if let Err(error) = index.add(*chunk_id as u64, vector) {
+                warn!("Failed to restore vector of chunk {}: {}", chunk_id, error);
+            }
+        }
+    }
+}
+
+/// Deletes chunks from both the chunk table and the index, returning the ids
+/// that had a row. Vectors are removed first and put back if the row delete
+/// fails, so a chunk is either gone from both stores or kept in both.
+async fn delete_chunks_by_id(
+    app_state: &AppState,
+    database_id: &DatabaseId,
+    chunk_ids: Vec<i64>,
+) -> Result<Vec<i64>, actix_web::Error> {
+    let settings = require_settings(&app_state.db_pool, database_id).await?;
+    if chunk_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let handle = open_index(app_state, database_id, &settings)?;
+    let index = handle.write().await;
+    let removed = remove_vectors(&index, &chunk_ids)?;
+
+    let table_name = database_id.table_name();
+    let deleted = app_state.db_pool.conn_mut(move |conn| {
+        let tx = conn.transaction()?;
+        let mut deleted = Vec::new();
+        for batch in chunk_ids.chunks(MAX_SQL_PARAMS) {
+            let placeholders = vec!["?"; batch.len()].join(", ");
+            let mut statement = tx.prepare(&format!(
+                "DELETE FROM {} WHERE chunk_id IN ({}) RETURNING chunk_id",
+                table_name, placeholders
+            ))?;
+            for chunk_id in statement.query_map(params_from_iter(batch), |row| row.get::<_, i64>(0))? {
+                deleted.push(chunk_id?);
+            }
+        }
+        tx.commit()?;
+        Ok(deleted)
+    }).await;
+
+    let deleted = match deleted {
+        Ok(deleted) => deleted,
+        Err(error) => {
+            restore_vectors(&index, &removed);
+            return Err(actix_web::error::ErrorInternalServerError(error));
+        }
+    };
+
+    // Ids that matched nothing leave the database as it was, so its
+    // revision, and the cursors issued for it, stay valid.
+    if !deleted.is_empty() || !removed.is_empty() {
+        index.mark_dirty();
+        touch_database(&app_state.db_pool, database_id).await?;
+    }
+    Ok(deleted)
+}
+
+#[api_operation(summary = "Delete a chunk by id")]
+async fn delete_chunk(
+    app_state: web::Data<Arc<AppState>>,
+    path: web::Path<(String, i64)>,
+) -> actix_web::Result<HttpResponse> {
+    let (database_id, chunk_id) = path.into_inner();
+    let database_id: DatabaseId = database_id.parse().map_err(actix_web::error::ErrorBadRequest)?;
+
+    if delete_chunks_by_id(&app_state, &database_id, vec![chunk_id]).await?.is_empty() {
+        return Err(actix_web::error::ErrorNotFound(format!("chunk {} does not exist", chunk_id)));
+    }
+
+    Ok(HttpResponse::Ok().json(json!({"status": "success", "message": "Chunk deleted successfully"})))
+}
+
+#[api_operation(summary = "Delete several chunks by id")]
+async fn delete_chunks(
+    app_state: web::Data<Arc<AppState>>,
+    database_id: web::Path<String>,
+    request: web::Json<DeleteChunksRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let database_id: DatabaseId = database_id.parse().map_err(actix_web::error::ErrorBadRequest)?;
+
+    let deleted_ids = delete_chunks_by_id(&app_state, &database_id, request.into_inner().chunk_ids).await?;
+    Ok(HttpResponse::Ok().json(json!({ "deleted_ids": deleted_ids })))
+}
+
+#[api_operation(summary = "Update a chunk in place")]
+async fn update_chunk(
+    app_state: web::Data<Arc<AppState>>,
+    path: web::Path<(String, i64)>,
+    request: web::Json<UpdateChunkRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let (database_id, chunk_id) = path.into_inner();
+    let database_id: DatabaseId = database_id.parse().map_err(actix_web::error::ErrorBadRequest)?;
+    let request = request.into_inner();
+
+    let settings = require_settings(&app_state.db_pool, &database_id).await?;
+    if let Some(embedding) = &request.embedding {
+        check_dimensions(&settings, embedding)?;
+    }
+
+    // Every writer holds the index lock, so the version cannot move under us.
+    let handle = open_index(&app_state, &database_id, &settings)?;
+    let index = handle.write().await;
+
+    let table_name = database_id.table_name();
+    let current_version: Option<i64> = app_state.db_pool.conn({
+        let table_name = table_name.clone();
+        move |conn| {
+            conn.query_row(
+                &format!("SELECT version FROM {} WHERE chunk_id = ?", table_name),
+                [chunk_id],
+                |row| row.get(0),
+            ).optional()
+        }
+    }).await.map_err(actix_web::error::ErrorInternalServerError)?;
+
+    let Some(current_version) = current_version else {
+        return Err(actix_web::error::ErrorNotFound(format!("chunk {} does not exist", chunk_id)));
+    };
+    if let Some(version) = request.version {
+        if version != current_version {
+            return Err(actix_web::error::ErrorConflict(format!(
+                "chunk {} is at version {}, not {}",
+                chunk_id, current_version, version
+            )));
+        }
+    }
+
+    // Swap the vector first so a failed row update can put the old one back.
+    let removed = match &request.embedding {
+        Some(embedding) => {
+            let removed = remove_vectors(&index, &[chunk_id])?;
+            let added = index.reserve(index.size() + 1).and_then(|_| index.add(chunk_id as u64, embedding));
+            if let Err(error) = added {
+                restore_vectors(&index, &removed);
+                return Err(actix_web::error::ErrorInternalServerError(error));
+            }
+            Some(removed)
+        }
+        None => None,
+    };
+
+    let (text, metadata) = (request.text, serialize_metadata(request.metadata));
+    let embedding = request.embedding.as_deref().map(embedding_to_blob);
+    let updated = app_state.db_pool.conn(move |conn| {
+        conn.query_row(
+            &format!(
+                "UPDATE {} SET text = COALESCE(?, text), metadata = COALESCE(?, metadata),
+                     embedding = COALESCE(?, embedding), version = version + 1
+                 WHERE chunk_id = ? AND version = ? RETURNING version",
+                table_name
+            ),
+            params![text, metadata, embedding, chunk_id, current_version],
+            |row| row.get::<_, i64>(0),
+        )
+    }).await;
+
+    let version = match updated {
+        Ok(version) => version,
+        Err(error) => {
+            if let Some(removed) = removed {
+                let _ = index.remove(chunk_id as u64);
+                restore_vectors(&index, &removed);
+            }
+            return Err(actix_web::error::ErrorInternalServerError(error));
+        }
+    };
+
+    index.mark_dirty();
+    touch_database(&app_state.db_pool, &database_id).await?;
+    drop(index);
+
+    Ok(HttpResponse::Ok().json(json!({ "chunk_id": chunk_id, "version": version })))
+}
+
+/// A chunk row as it was before an insert batch overwrote it.
+struct OverwrittenRow {
+    chunk_id: i64,
+    text: String,
+    metadata: Option<String>,
+    version: i64,
+    embedding: Option<Vec<u8>>,
 }
 
-async fn ensure_table_exists(db_pool: &Pool, database_id: &str) -> Result<(), actix_web::Error> {
-    let table_name = format!("chunks_{}", database_id);
-    db_pool.conn(move |conn| {
-        conn.execute(
-            &format!("CREATE TABLE IF NOT EXISTS {} (
-                chunk_id INTEGER PRIMARY KEY AUTOINCREMENT,
-                text TEXT,
-                metadata TEXT
-            )", table_name),
-            [],
-        )
-    }).await.map_err(actix_web::error::ErrorInternalServerError)?;
+/// Adds the vectors of a committed insert batch, recording the keys it adds
+/// and the vectors it replaces so that the caller can undo it.
+fn add_batch(
+    index: &Index,
+    chunk_ids: &[i64],
+    chunks: &[ChunkData],
+    undo: &mut VectorUndo,
+    replaced: &mut Vec<(i64, Vec<f32>)>,
+) -> Result<(), actix_web::Error> {
+    for (&chunk_id, chunk) in chunk_ids.iter().zip(chunks) {
+        let key = chunk_id as u64;
+        match undo.step(key, index.contains(key)) {
+            VectorStep::Add => {}
+            VectorStep::Replace => replaced.extend(remove_vectors(index, &[chunk_id])?),
+            VectorStep::Supersede => {
+                index.remove(key).map_err(actix_web::error::ErrorInternalServerError)?;
+            }
+        }
+        index.add(key, &chunk.embedding).map_err(actix_web::error::ErrorInternalServerError)?;
+    }
     Ok(())
 }
 
-fn load_or_create_index(database_id: &str) -> Result<Index, actix_web::Error> {
-    let index_file = format!("{}.usearch", database_id);
-    let options = IndexOptions {
-        dimensions: 2,
-        metric: MetricKind::IP,
-        quantization: ScalarKind::F32,
-        connectivity: 0,
-        expansion_add: 0,
-        expansion_search: 0,
-        multi: true,
-    };
-    let index: Index = new_index(&options).map_err(actix_web::error::ErrorInternalServerError)?;
-    
-    if std::path::Path::new(&index_file).exists() {
-        index.load(&index_file).map_err(actix_web::error::ErrorInternalServerError)?;
-    }
-    
-    Ok(index)
+/// Undoes a committed insert batch in the chunk table: deletes the rows it
+/// created and puts back the ones it overwrote.
+async fn revert_insert(
+    db_pool: &Pool,
+    database_id: &DatabaseId,
+    undo: RowUndo<OverwrittenRow>,
+) -> Result<(), async_sqlite::Error> {
+    let table_name = database_id.table_name();
+    db_pool.conn_mut(move |conn| {
+        let tx = conn.transaction()?;
+        for chunk_id in undo.created {
+            tx.execute(&format!("DELETE FROM {} WHERE chunk_id = ?", table_name), [chunk_id])?;
+        }
+        for row in undo.overwritten {
+            tx.execute(
+                &format!("UPDATE {} SET text = ?, metadata = ?, version = ?, embedding = ? WHERE chunk_id = ?", table_name),
+                params![row.text, row.metadata, row.version, row.embedding, row.chunk_id],
+            )?;
+        }
+        tx.commit()
+    }).await
 }
 
 #[api_operation(summary = "Insert chunks into the database")]
@@ -96,46 +1489,269 @@ async fn insert_chunk(
     request: web::Json<InsertChunkRequest>,
 ) -> actix_web::Result<HttpResponse> {
 
+    let settings = require_settings(&app_state.db_pool, &request.database_id).await?;
+
+    for chunk in &request.chunks {
+        check_dimensions(&settings, &chunk.embedding)?;
+    }
+
     log::debug!("Loading index");
 
-    let mut index = load_or_create_index(&request.database_id)?;
+    let handle = open_index(&app_state, &request.database_id, &settings)?;
+    let index = handle.write().await;
 
     index.reserve(request.chunks.len() + index.size()).map_err(actix_web::error::ErrorInternalServerError)?;
 
     log::debug!("Loaded index {}", &request.database_id);
+    
+    let table_name = request.database_id.table_name();
+    let chunks = request.chunks.clone();
+
+    // A batch is all or nothing: rows are written in one transaction and
+    // vectors only added once it commits. If adding the vectors fails, the
+    // index changes are undone and the committed rows reverted.
+    log::debug!("inserting into database");
+    let (chunk_ids, row_undo) = app_state.db_pool.conn_mut(move |conn| {
+        let tx = conn.transaction()?;
+        let mut chunk_ids = Vec::new();
+        let mut undo = RowUndo::new();
+        {
+            let mut find = tx.prepare(&format!(
+                "SELECT chunk_id, text, metadata, version, embedding FROM {} WHERE external_id = ?",
+                table_name
+            ))?;
+            let mut upsert = tx.prepare(&format!(
+                "INSERT INTO {} (external_id, text, metadata, embedding) VALUES (?, ?, ?, ?)
+                 ON CONFLICT (external_id) DO UPDATE SET
+                     text = excluded.text, metadata = excluded.metadata, embedding = excluded.embedding,
+                     version = version + 1
+                 RETURNING chunk_id",
+                table_name
+            ))?;
+            for chunk in chunks {
+                let existing = match &chunk.external_id {
+                    Some(external_id) => find.query_row([external_id], |row| {
+                        Ok(OverwrittenRow {
+                            chunk_id: row.get(0)?,
+                            text: row.get(1)?,
+                            metadata: row.get(2)?,
+                            version: row.get(3)?,
+                            embedding: row.get(4)?,
+                        })
+                    }).optional()?,
+                    None => None,
+                };
+                let chunk_id: i64 = upsert.query_row(
+                    params![
+                        chunk.external_id,
+                        chunk.text,
+                        serialize_metadata(chunk.metadata),
+                        embedding_to_blob(&chunk.embedding),
+                    ],
+                    |row| row.get(0),
+                )?;
+                undo.record(chunk_id, existing);
+                chunk_ids.push(chunk_id);
+            }
+        }
+        tx.commit()?;
+        Ok((chunk_ids, undo))
+    }).await.map_err(actix_web::error::ErrorInternalServerError)?;
 
-    ensure_table_exists(&app_state.db_pool, &request.database_id).await?;
+    log::debug!("inserting into vector index");
 
-    log::debug!("Ensured table exists {}", &request.database_id);
-    
-    let table_name = format!("chunks_{}", request.database_id);
+    let mut vector_undo = VectorUndo::default();
+    let mut replaced = Vec::new();
+    if let Err(error) = add_batch(&index, &chunk_ids, &request.chunks, &mut vector_undo, &mut replaced) {
+        for key in vector_undo.added {
+            if let Err(error) = index.remove(key) {
+                warn!("Failed to remove vector of chunk {}: {}", key, error);
+            }
+        }
+        restore_vectors(&index, &replaced);
+        if let Err(error) = revert_insert(&app_state.db_pool, &request.database_id, row_undo).await {
+            warn!("Failed to revert inserted chunks of {}: {}", request.database_id, error);
+        }
+        return Err(error);
+    }
 
-    let mut inserted_ids = Vec::new();
+    // An empty batch writes nothing, so it leaves the revision as it was.
+    if !chunk_ids.is_empty() {
+        index.mark_dirty();
+        touch_database(&app_state.db_pool, &request.database_id).await?;
+    }
+    let memory_usage = index.memory_usage();
+    drop(index);
+    app_state.indexes.evict_over_budget();
 
-    for chunk in &request.chunks {
-        let chunk = chunk.clone();
-        let table_name = table_name.clone();
+    Ok(HttpResponse::Ok().json(json!({
+        "inserted_ids": chunk_ids,
+        "memory_usage": memory_usage,
+    })))
+}
 
-        log::debug!("inserting into database");
-        let chunk_id: i64 = app_state.db_pool.conn(move |conn| {
-            conn.query_row(
-                &format!("INSERT INTO {} (text, metadata) VALUES (?, ?) RETURNING chunk_id", table_name),
-                [&chunk.text, &chunk.metadata],
-                |row| row.get(0),
-            )
-        }).await.map_err(actix_web::error::ErrorInternalServerError)?;
-        
-        log::debug!("inserting into vector index");
+/// Candidates fetched per requested result: keyword hits to fuse, vector hits
+/// to fill groups from before the pool grows, and hits to re-rank with MMR
+/// when `candidates` is unset.
+const CANDIDATES_PER_RESULT: usize = 4;
+
+/// Ranks vector candidates for each query embedding, best first, applying
+/// thresholds and fusing keyword hits. Returns the rankings and whether they
+/// hold every candidate, so that a larger `pool` would not add any.
+async fn rank_candidates(
+    app_state: &AppState,
+    request: &SearchRequest,
+    settings: &DatabaseSettings,
+    handle: &IndexHandle,
+    allowed: Option<&HashSet<u64>>,
+    keyword_query: Option<&str>,
+    pool: usize,
+) -> Result<(Vec<Vec<(u64, f32)>>, bool), actix_web::Error> {
+    let (keyword_hits, candidates) = match keyword_query {
+        Some(query) => {
+            // Fusing draws on more candidates than requested, but no more than
+            // grouping considers unless the pool itself is larger.
+            let limit = pool.saturating_mul(CANDIDATES_PER_RESULT).min(pool.max(grouping::MAX_CANDIDATES));
+            let hits = keyword_search(&app_state.db_pool, &request.database_id, query.to_string(), request.filter.as_ref(), limit).await?;
+            (Some(hits), limit)
+        }
+        None => (None, pool),
+    };
+    let mut exhausted = keyword_hits.as_ref().is_none_or(|hits| hits.len() < candidates);
+
+    let all_matches = match request.expansion_search {
+        // Expansion is index-wide state, so overriding it needs exclusive access.
+        Some(expansion_search) => {
+            let index = handle.write().await;
+            let previous = index.expansion_search();
+            index.change_expansion_search(expansion_search);
+            let all_matches = search_index(&index, settings, &request.embeddings, candidates, allowed);
+            index.change_expansion_search(previous);
+            all_matches?
+        }
+        None => {
+            let index = handle.read().await;
+            search_index(&index, settings, &request.embeddings, candidates, allowed)?
+        }
+    };
+
+    let rankings = all_matches.iter().map(|matches| {
+        // Thresholds apply to vector similarity, so hybrid searches apply them
+        // before fusing.
+        let ranking: Vec<(u64, f32)> = matches.keys.iter().copied()
+            .zip(matches.distances.iter().copied())
+            .filter(|(_, distance)| request.max_distance.is_none_or(|max_distance| *distance <= max_distance))
+            .map(|(chunk_id, distance)| (chunk_id, settings.metric.score(distance)))
+            .filter(|(_, score)| request.min_score.is_none_or(|min_score| *score >= min_score))
+            .collect();
+        // Past a threshold, further candidates would only be worse.
+        exhausted &= matches.keys.len() < candidates || ranking.len() < matches.keys.len();
+        match &keyword_hits {
+            // A keyword hit has no vector similarity to hold against a
+            // threshold unless it is among the vector hits that passed it.
+            Some(keyword_hits) if request.min_score.is_some() || request.max_distance.is_some() => {
+                let passed: HashSet<u64> = ranking.iter().map(|(chunk_id, _)| *chunk_id).collect();
+                let keyword_hits: Vec<(u64, f32)> =
+                    keyword_hits.iter().filter(|(chunk_id, _)| passed.contains(chunk_id)).copied().collect();
+                request.fusion.fuse(&ranking, &keyword_hits, pool)
+            }
+            Some(keyword_hits) => request.fusion.fuse(&ranking, keyword_hits, pool),
+            None => ranking,
+        }
+    }).collect();
+    Ok((rankings, exhausted))
+}
 
-        index.add(chunk_id as u64, &chunk.embedding).map_err(actix_web::error::ErrorInternalServerError)?;
+/// Returns the value of a metadata field for each of `chunk_ids` that has it.
+async fn group_values(
+    db_pool: &Pool,
+    database_id: &DatabaseId,
+    field: &str,
+    chunk_ids: Vec<u64>,
+) -> Result<HashMap<u64, Value>, actix_web::Error> {
+    if chunk_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let path = filter::json_path(field).map_err(actix_web::error::ErrorBadRequest)?;
+    let table_name = database_id.table_name();
+    db_pool.conn(move |conn| {
+        let mut values = HashMap::new();
+        // One parameter of each statement is the path.
+        for batch in chunk_ids.chunks(MAX_SQL_PARAMS - 1) {
+            let placeholders = vec!["?"; batch.len()].join(", ");
+            let mut statement = conn.prepare(&format!(
+                "SELECT chunk_id, CASE WHEN json_valid(metadata) THEN metadata -> ? END FROM {} WHERE chunk_id IN ({})",
+                table_name, placeholders
+            ))?;
+            let params = std::iter::once(path.clone()).chain(batch.iter().map(|chunk_id| SqlValue::Integer(*chunk_id as i64)));
+            let rows = statement.query_map(params_from_iter(params), |row| {
+                Ok((row.get::<_, i64>(0)? as u64, row.get::<_, Option<String>>(1)?))
+            })?;
+            for row in rows {
+                if let (chunk_id, Some(value)) = row? {
+                    values.insert(chunk_id, serde_json::from_str(&value).unwrap_or(Value::Null));
+                }
+            }
+        }
+        Ok(values)
+    }).await.map_err(actix_web::error::ErrorInternalServerError)
+}
 
-        inserted_ids.push(chunk_id);
+/// Reads the vectors of `chunk_ids` back from the index.
+async fn reconstruct_embeddings(
+    handle: &IndexHandle,
+    chunk_ids: impl Iterator<Item = u64>,
+) -> Result<HashMap<u64, Vec<f32>>, actix_web::Error> {
+    let index = handle.read().await;
+    let mut embeddings = HashMap::new();
+    for chunk_id in chunk_ids {
+        if let Some(embedding) = reconstruct_embedding(&index, chunk_id as i64)? {
+            embeddings.insert(chunk_id, embedding);
+        }
     }
+    Ok(embeddings)
+}
 
-    let index_file = format!("{}.usearch", request.database_id);
-    index.save(&index_file).map_err(actix_web::error::ErrorInternalServerError)?;
+/// Most parameters SQLite binds in one statement.
+const MAX_SQL_PARAMS: usize = 32766;
 
-    Ok(HttpResponse::Ok().json(json!({ "inserted_ids": inserted_ids })))
+/// Reads the rows of all `chunk_ids`, or none if the request excludes both
+/// text and metadata.
+async fn fetch_hit_rows(
+    db_pool: &Pool,
+    request: &SearchRequest,
+    chunk_ids: impl Iterator<Item = u64>,
+) -> Result<HashMap<i64, Chunk>, actix_web::Error> {
+    if !(request.include_text || request.include_metadata) {
+        return Ok(HashMap::new());
+    }
+    fetch_chunks(db_pool, &request.database_id, chunk_ids.map(|chunk_id| chunk_id as i64).collect()).await
+}
+
+/// Turns ranked `(chunk_id, score)` pairs into the hits returned to the client,
+/// keeping the ranking order. Hits whose row is missing are skipped.
+fn to_hits(
+    request: &SearchRequest,
+    ranking: Vec<(u64, f32)>,
+    rows: &HashMap<i64, Chunk>,
+    embeddings: &HashMap<u64, Vec<f32>>,
+) -> Vec<SearchResult> {
+    let fetched = request.include_text || request.include_metadata;
+    ranking.into_iter().filter_map(|(chunk_id, score)| {
+        let row = rows.get(&(chunk_id as i64));
+        if fetched && row.is_none() {
+            warn!("Chunk {} of database {} is indexed but has no row", chunk_id, request.database_id);
+            return None;
+        }
+        Some(SearchResult {
+            chunk_id: chunk_id as i64,
+            text: row.filter(|_| request.include_text).map(|row| row.text.clone()),
+            metadata: row.filter(|_| request.include_metadata).map(|row| row.metadata.clone().unwrap_or(Value::Null)),
+            score,
+            embedding: request.include_embedding.then(|| embeddings.get(&chunk_id).cloned()).flatten(),
+            snippet: None,
+        })
+    }).collect()
 }
 
 #[api_operation(summary = "Search for chunks")]
@@ -143,40 +1759,685 @@ async fn search(
     app_state: web::Data<Arc<AppState>>,
     request: web::Json<SearchRequest>,
 ) -> actix_web::Result<HttpResponse> {
-    let index = load_or_create_index(&request.database_id)?;
+    let settings = require_settings(&app_state.db_pool, &request.database_id).await?;
 
-    ensure_table_exists(&app_state.db_pool, &request.database_id).await?;
-    let table_name = format!("chunks_{}", request.database_id);
+    for query_embedding in &request.embeddings {
+        check_dimensions(&settings, query_embedding)?;
+    }
 
-    let mut all_results = Vec::new();
+    // The revision is read before searching, so a write racing with this
+    // search makes the cursor stale rather than letting it skip hits.
+    let revision = load_revision(&app_state.db_pool, &request.database_id).await?;
+    let offset = match &request.cursor {
+        Some(cursor) => {
+            let cursor: Cursor = cursor.parse().map_err(actix_web::error::ErrorBadRequest)?;
+            if cursor.revision != revision {
+                return Err(actix_web::error::ErrorConflict("cursor is stale: the database changed since it was issued"));
+            }
+            cursor.offset
+        }
+        None => 0,
+    };
+    if request.num_results == 0 {
+        return Err(actix_web::error::ErrorBadRequest("num_results must be greater than zero"));
+    }
+    // One hit, or group, past the page tells whether there is a next one.
+    let depth = offset.checked_add(request.num_results)
+        .filter(|&end| end <= MAX_SEARCH_DEPTH)
+        .ok_or_else(|| actix_web::error::ErrorBadRequest(format!(
+            "a search can page through at most {} hits", MAX_SEARCH_DEPTH
+        )))? + 1;
 
-    for query_embedding in &request.embeddings {
-        let results = index.search(query_embedding, request.num_results).map_err(actix_web::error::ErrorInternalServerError)?;
-        
-        let mut ranked_chunks = Vec::new();
-        for (chunk_id, score) in results.keys.iter().zip(results.distances.iter()) {
-            let chunk_id = *chunk_id;
-            let score = *score;
-            let table_name = table_name.clone();
-            let chunk = app_state.db_pool.conn(move |conn| {
-                conn.query_row(
-                    &format!("SELECT text, metadata FROM {} WHERE chunk_id = ?", table_name),
-                    [chunk_id.to_string()],
-                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
-                )
+    if let Some(mmr) = &request.mmr {
+        mmr.validate().map_err(actix_web::error::ErrorBadRequest)?;
+    }
+    if let Some(group_by) = &request.group_by {
+        group_by.validate().map_err(actix_web::error::ErrorBadRequest)?;
+        if request.mmr.is_some() {
+            return Err(actix_web::error::ErrorBadRequest("mmr and group_by cannot be combined"));
+        }
+    }
+    let keyword_query = match &request.text_query {
+        Some(text_query) => {
+            request.fusion.validate().map_err(actix_web::error::ErrorBadRequest)?;
+            let query = full_text::keyword_query(text_query)
+                .ok_or_else(|| actix_web::error::ErrorBadRequest("text_query has no terms"))?;
+            Some(query)
+        }
+        None => None,
+    };
+
+    let allowed = match &request.filter {
+        Some(filter) => Some(matching_chunk_ids(&app_state.db_pool, &request.database_id, filter).await?),
+        None => None,
+    };
+
+    let handle = open_index(&app_state, &request.database_id, &settings)?;
+
+    if let Some(group_by) = &request.group_by {
+        // Grow the pool until every query has its groups filled, as one
+        // group may take up many of the top candidates.
+        let mut pool = group_by.pool_size(depth);
+        let all_groups = loop {
+            let (rankings, exhausted) = rank_candidates(
+                &app_state, &request, &settings, &handle, allowed.as_ref(), keyword_query.as_deref(), pool,
+            ).await?;
+            let chunk_ids: HashSet<u64> = rankings.iter().flatten().map(|(chunk_id, _)| *chunk_id).collect();
+            let values = group_values(&app_state.db_pool, &request.database_id, &group_by.field, chunk_ids.into_iter().collect()).await?;
+            let all_groups: Vec<Vec<grouping::Group>> = rankings.iter()
+                .map(|ranking| group_by.group(ranking, &values, depth))
+                .collect();
+            let filled = all_groups.iter().all(|groups| group_by.is_filled(groups, depth));
+            if filled || exhausted || pool >= grouping::MAX_CANDIDATES {
+                break all_groups;
+            }
+            pool = (pool * 2).min(grouping::MAX_CANDIDATES);
+        };
+
+        let has_more = all_groups.iter().any(|groups| groups.len() >= depth);
+        let all_groups: Vec<Vec<grouping::Group>> = all_groups.into_iter()
+            .map(|groups| groups.into_iter().skip(offset).take(request.num_results).collect())
+            .collect();
+
+        let embeddings = if request.include_embedding {
+            let chunk_ids = all_groups.iter().flatten().flat_map(|(_, hits)| hits.iter().map(|(chunk_id, _)| *chunk_id));
+            reconstruct_embeddings(&handle, chunk_ids).await?
+        } else {
+            HashMap::new()
+        };
+
+        let chunk_ids = all_groups.iter().flatten().flat_map(|(_, hits)| hits.iter().map(|(chunk_id, _)| *chunk_id));
+        let rows = fetch_hit_rows(&app_state.db_pool, &request, chunk_ids).await?;
+        let response_groups = all_groups.into_iter().map(|groups| {
+            groups.into_iter()
+                .map(|(value, hits)| SearchGroup { value, hits: to_hits(&request, hits, &rows, &embeddings) })
+                .collect()
+        }).collect();
+
+        let next_cursor = has_more.then(|| Cursor { revision, offset: offset + request.num_results }.to_string());
+        return Ok(HttpResponse::Ok().json(SearchResponse { results: None, groups: Some(response_groups), next_cursor }));
+    }
+
+    let pool = request.mmr.as_ref().map_or(depth, |mmr| mmr.pool_size(depth));
+    let (rankings, _) = rank_candidates(
+        &app_state, &request, &settings, &handle, allowed.as_ref(), keyword_query.as_deref(), pool,
+    ).await?;
+
+    let mut embeddings = if request.include_embedding || request.mmr.is_some() {
+        reconstruct_embeddings(&handle, rankings.iter().flatten().map(|(chunk_id, _)| *chunk_id)).await?
+    } else {
+        HashMap::new()
+    };
+
+    let rankings = match request.mmr.clone() {
+        Some(mmr) => {
+            // Reranking compares every pick with the rest of the pool, too
+            // much work to hold up the async workers with.
+            let queries = request.embeddings.clone();
+            let metric = settings.metric;
+            let (reranked, vectors) = web::block(move || {
+                let similarity = |a: &[f32], b: &[f32]| metric.score(metric.distance(a, b));
+                let reranked: Vec<_> = rankings
+                    .iter()
+                    .zip(&queries)
+                    .map(|(ranking, query)| mmr.rerank(ranking, &embeddings, query, similarity, depth))
+                    .collect();
+                (reranked, embeddings)
             }).await.map_err(actix_web::error::ErrorInternalServerError)?;
+            embeddings = vectors;
+            reranked
+        }
+        None => rankings,
+    };
+
+    let mut has_more = false;
+    let rankings: Vec<Vec<(u64, f32)>> = rankings.into_iter().map(|ranking| {
+        has_more |= ranking.len() >= depth;
+        ranking.into_iter().skip(offset).take(request.num_results).collect()
+    }).collect();
+
+    let rows = fetch_hit_rows(&app_state.db_pool, &request, rankings.iter().flatten().map(|(chunk_id, _)| *chunk_id)).await?;
+    let all_results = rankings.into_iter().map(|ranking| to_hits(&request, ranking, &rows, &embeddings)).collect();
+
+    let next_cursor = has_more.then(|| Cursor { revision, offset: offset + request.num_results }.to_string());
+    Ok(HttpResponse::Ok().json(SearchResponse { results: Some(all_results), groups: None, next_cursor }))
+}
+
+#[api_operation(summary = "Search chunk text by keywords")]
+async fn text_search(
+    app_state: web::Data<Arc<AppState>>,
+    request: web::Json<TextSearchRequest>,
+) -> actix_web::Result<HttpResponse> {
+    require_settings(&app_state.db_pool, &request.database_id).await?;
+
+    if !(1..=MAX_SEARCH_DEPTH).contains(&request.num_results) {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "num_results must be between 1 and {}", MAX_SEARCH_DEPTH
+        )));
+    }
+
+    let snippet = &request.snippet;
+    if !(1..=64).contains(&snippet.max_tokens) {
+        return Err(actix_web::error::ErrorBadRequest("snippet max_tokens must be between 1 and 64"));
+    }
+
+    let mut params = vec![
+        SqlValue::Text(snippet.start.clone()),
+        SqlValue::Text(snippet.end.clone()),
+        SqlValue::Integer(snippet.max_tokens as i64),
+        SqlValue::Text(request.query.clone()),
+    ];
+    let condition = match &request.filter {
+        Some(filter) => filter.to_sql(&mut params).map_err(actix_web::error::ErrorBadRequest)?,
+        None => "1".to_string(),
+    };
+    params.push(SqlValue::Integer(request.num_results as i64));
+
+    let sql = format!(
+        "SELECT chunk_id, {table}.text, metadata, -bm25({fts}), snippet({fts}, 0, ?, ?, '…', ?)
+         FROM {fts} JOIN {table} ON chunk_id = {fts}.rowid
+         WHERE {fts} MATCH ? AND ({condition}) ORDER BY bm25({fts}) LIMIT ?",
+        fts = full_text::text_index_table(&request.database_id),
+        table = request.database_id.table_name(),
+        condition = condition,
+    );
+    let results = app_state.db_pool.conn(move |conn| {
+        let mut statement = conn.prepare(&sql)?;
+        // FTS5 parses the query once the statement runs, so only errors from
+        // running it can be the caller's.
+        let results = statement.query_map(params_from_iter(params), |row| {
+            Ok(SearchResult {
+                chunk_id: row.get(0)?,
+                text: row.get(1)?,
+                metadata: Some(parse_metadata(row.get(2)?).unwrap_or(Value::Null)),
+                score: row.get::<_, f64>(3)? as f32,
+                embedding: None,
+                snippet: row.get(4)?,
+            })
+        }).and_then(|results| results.collect::<rusqlite::Result<Vec<_>>>());
+        match results {
+            Err(error) if full_text::is_query_error(&error) => Ok(Err(error)),
+            results => results.map(Ok),
+        }
+    }).await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .map_err(|error| actix_web::error::ErrorBadRequest(format!("invalid query: {}", error)))?;
+
+    Ok(HttpResponse::Ok().json(SearchResponse { results: Some(vec![results]), groups: None, next_cursor: None }))
+}
+
+/// Chunk ids of a database and the largest id ever handed out, which bounds
+/// the keys its index may hold.
+async fn load_chunk_ids(db_pool: &Pool, database_id: &DatabaseId) -> Result<(Vec<i64>, i64), actix_web::Error> {
+    let table_name = database_id.table_name();
+    db_pool.conn(move |conn| {
+        let mut statement = conn.prepare(&format!("SELECT chunk_id FROM {} ORDER BY chunk_id", table_name))?;
+        let chunk_ids = statement.query_map([], |row| row.get(0))?.collect::<Result<Vec<i64>, _>>()?;
+        let sequence: Option<i64> = conn.query_row(
+            "SELECT seq FROM sqlite_sequence WHERE name = ?",
+            [&table_name],
+            |row| row.get(0),
+        ).optional()?;
+        let max_id = sequence.unwrap_or(0).max(chunk_ids.last().copied().unwrap_or(0));
+        Ok((chunk_ids, max_id))
+    }).await.map_err(actix_web::error::ErrorInternalServerError)
+}
+
+/// Compares the chunk rows of a database with the keys of its index.
+async fn inspect_database(
+    db_pool: &Pool,
+    database_id: &DatabaseId,
+    index: &Index,
+) -> Result<(Vec<i64>, Discrepancies), actix_web::Error> {
+    let (chunk_ids, max_id) = load_chunk_ids(db_pool, database_id).await?;
+    let discrepancies = Discrepancies::find(&chunk_ids, max_id, |key| index.count(key as u64), index.size());
+    Ok((chunk_ids, discrepancies))
+}
+
+/// Loads the stored embeddings of `chunk_ids`, or of every chunk if `None`.
+/// Chunks stored before embeddings were kept have `None`.
+async fn load_embeddings(
+    db_pool: &Pool,
+    database_id: &DatabaseId,
+    chunk_ids: Option<Vec<i64>>,
+    dimensions: usize,
+) -> Result<Vec<(i64, Option<Vec<f32>>)>, actix_web::Error> {
+    let table_name = database_id.table_name();
+    db_pool.conn(move |conn| {
+        let decode = |row: &Row| -> rusqlite::Result<(i64, Option<Vec<f32>>)> {
+            let blob: Option<Vec<u8>> = row.get(1)?;
+            Ok((row.get(0)?, blob.and_then(|blob| embedding_from_blob(&blob, dimensions))))
+        };
+        let mut embeddings = Vec::new();
+        match chunk_ids {
+            None => {
+                let mut statement = conn.prepare(&format!("SELECT chunk_id, embedding FROM {} ORDER BY chunk_id", table_name))?;
+                for row in statement.query_map([], decode)? {
+                    embeddings.push(row?);
+                }
+            }
+            Some(chunk_ids) => {
+                for batch in chunk_ids.chunks(MAX_SQL_PARAMS) {
+                    let placeholders = vec!["?"; batch.len()].join(", ");
+                    let mut statement = conn.prepare(&format!(
+                        "SELECT chunk_id, embedding FROM {} WHERE chunk_id IN ({})",
+                        table_name, placeholders
+                    ))?;
+                    for row in statement.query_map(params_from_iter(batch), decode)? {
+                        embeddings.push(row?);
+                    }
+                }
+            }
+        }
+        Ok(embeddings)
+    }).await.map_err(actix_web::error::ErrorInternalServerError)
+}
+
+/// Removes vectors without a row and puts back the stored embedding of rows
+/// without a vector, or with several. Rows without a vector whose embedding
+/// was never stored, listed in `orphan_rows`, are deleted. Unaccounted vectors
+/// cannot be found by key and are left alone. The caller saves the index.
+async fn repair_database(
+    db_pool: &Pool,
+    database_id: &DatabaseId,
+    index: &Index,
+    discrepancies: &Discrepancies,
+    stored: &HashMap<i64, Vec<f32>>,
+    orphan_rows: Vec<i64>,
+) -> Result<(), actix_web::Error> {
+    for &chunk_id in &discrepancies.orphan_keys {
+        index.remove(chunk_id as u64).map_err(actix_web::error::ErrorInternalServerError)?;
+    }
+    index.reserve(index.size() + stored.len()).map_err(actix_web::error::ErrorInternalServerError)?;
+    for (&chunk_id, embedding) in stored {
+        index.remove(chunk_id as u64)
+            .and_then(|_| index.add(chunk_id as u64, embedding))
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+    }
+
+    let table_name = database_id.table_name();
+    db_pool.conn_mut(move |conn| {
+        let tx = conn.transaction()?;
+        for batch in orphan_rows.chunks(MAX_SQL_PARAMS) {
+            let placeholders = vec!["?"; batch.len()].join(", ");
+            tx.execute(
+                &format!("DELETE FROM {} WHERE chunk_id IN ({})", table_name, placeholders),
+                params_from_iter(batch),
+            )?;
+        }
+        tx.commit()
+    }).await.map_err(actix_web::error::ErrorInternalServerError)?;
+
+    touch_database(db_pool, database_id).await
+}
 
-            ranked_chunks.push(SearchResult {
-                text: chunk.0,
-                metadata: chunk.1,
-                score,
-            });
+/// Checks that every chunk row of a database has exactly one vector in the
+/// index and every vector a row, repairing what can be repaired if asked to.
+async fn verify_database(app_state: &AppState, database_id: &DatabaseId, repair: bool) -> Result<VerifyReport, actix_web::Error> {
+    let settings = require_settings(&app_state.db_pool, database_id).await?;
+    let handle = open_index(app_state, database_id, &settings)?;
+    // Writers hold this lock across their SQLite writes, so the rows cannot
+    // change while they are compared with the index.
+    let index = handle.write().await;
+    save_index(&app_state.db_pool, database_id, &index).await?;
+
+    let index_file_exists = std::path::Path::new(&database_id.index_file()).exists();
+    let index_size = index.size();
+    let (chunk_ids, discrepancies) = inspect_database(&app_state.db_pool, database_id, &index).await?;
+
+    let mut consistent = discrepancies.is_empty() && (index_file_exists || index_size == 0);
+    let repaired = repair && !consistent;
+    if repaired {
+        let damaged = [discrepancies.orphan_rows.as_slice(), &discrepancies.duplicate_keys].concat();
+        let stored: HashMap<i64, Vec<f32>> =
+            load_embeddings(&app_state.db_pool, database_id, Some(damaged), settings.dimensions).await?
+                .into_iter()
+                .filter_map(|(chunk_id, embedding)| Some((chunk_id, embedding?)))
+                .collect();
+        let orphan_rows: Vec<i64> = discrepancies.orphan_rows.iter()
+            .copied()
+            .filter(|chunk_id| !stored.contains_key(chunk_id))
+            .collect();
+        if !index_file_exists && index_size == 0 && !orphan_rows.is_empty() {
+            return Err(actix_web::error::ErrorConflict(format!(
+                "index file of database {} is missing; restore it before repairing, or the chunks without a stored embedding would be deleted",
+                database_id
+            )));
+        }
+        repair_database(&app_state.db_pool, database_id, &index, &discrepancies, &stored, orphan_rows).await?;
+        index.mark_dirty();
+        save_index(&app_state.db_pool, database_id, &index).await?;
+        consistent = inspect_database(&app_state.db_pool, database_id, &index).await?.1.is_empty();
+    }
+
+    Ok(VerifyReport {
+        database_id: database_id.clone(),
+        index_file_exists,
+        chunk_count: chunk_ids.len() as u64,
+        index_size,
+        discrepancies,
+        repaired,
+        consistent,
+    })
+}
+
+#[api_operation(summary = "Check that a database's chunks and vector index agree")]
+async fn verify(
+    app_state: web::Data<Arc<AppState>>,
+    database_id: web::Path<String>,
+    query: web::Query<VerifyQuery>,
+) -> actix_web::Result<HttpResponse> {
+    let database_id: DatabaseId = database_id.parse().map_err(actix_web::error::ErrorBadRequest)?;
+    let report = verify_database(&app_state, &database_id, query.repair).await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Resolves the options of a rebuild against the current settings. Unset
+/// fields keep their value, except that a new metric without a quantization
+/// gets the default quantization for that metric.
+fn rebuild_settings(current: &DatabaseSettings, options: DatabaseOptions) -> Result<DatabaseSettings, actix_web::Error> {
+    let options = DatabaseOptions {
+        metric: options.metric.or(Some(current.metric)),
+        quantization: options.quantization.or(options.metric.is_none().then_some(current.quantization)),
+        connectivity: options.connectivity.or(Some(current.connectivity)),
+        expansion_add: options.expansion_add.or(Some(current.expansion_add)),
+        expansion_search: options.expansion_search.or(Some(current.expansion_search)),
+    };
+    settings_from_options(&options, current.dimensions)
+}
+
+/// Gathers the vectors to rebuild a database's index from: each chunk's
+/// stored embedding, or its vector in the current index for chunks stored
+/// before embeddings were kept. Those are stored along the way, so later
+/// rebuilds no longer depend on the index. Fails if a chunk has neither.
+async fn collect_vectors(
+    db_pool: &Pool,
+    database_id: &DatabaseId,
+    dimensions: usize,
+    index: &Index,
+) -> Result<Vec<(i64, Vec<f32>)>, actix_web::Error> {
+    let mut vectors = Vec::new();
+    let mut recovered = Vec::new();
+    let mut missing = Vec::new();
+    for (chunk_id, embedding) in load_embeddings(db_pool, database_id, None, dimensions).await? {
+        match embedding {
+            Some(embedding) => vectors.push((chunk_id, embedding)),
+            None => match reconstruct_embedding(index, chunk_id)? {
+                Some(embedding) => {
+                    recovered.push((chunk_id, embedding_to_blob(&embedding)));
+                    vectors.push((chunk_id, embedding));
+                }
+                None => missing.push(chunk_id),
+            },
+        }
+    }
+    if let Some(chunk_id) = missing.first() {
+        return Err(actix_web::error::ErrorConflict(format!(
+            "{} chunks, e.g. {}, have neither a stored embedding nor a vector in the index; repair the database first",
+            missing.len(),
+            chunk_id
+        )));
+    }
+
+    let table_name = database_id.table_name();
+    db_pool.conn_mut(move |conn| {
+        let tx = conn.transaction()?;
+        {
+            let mut update = tx.prepare(&format!("UPDATE {} SET embedding = ? WHERE chunk_id = ?", table_name))?;
+            for (chunk_id, blob) in recovered {
+                update.execute(params![blob, chunk_id])?;
+            }
+        }
+        tx.commit()
+    }).await.map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(vectors)
+}
+
+/// Builds an index with a database's settings from `(chunk_id, embedding)`
+/// pairs, calling `progress` with the number added every so often.
+fn build_index(
+    settings: &DatabaseSettings,
+    vectors: &[(i64, Vec<f32>)],
+    mut progress: impl FnMut(usize),
+) -> Result<Index, actix_web::Error> {
+    let index = create_index(settings)?;
+    index.reserve(vectors.len()).map_err(actix_web::error::ErrorInternalServerError)?;
+    for (added, (chunk_id, embedding)) in vectors.iter().enumerate() {
+        index.add(*chunk_id as u64, embedding).map_err(actix_web::error::ErrorInternalServerError)?;
+        if (added + 1) % 1024 == 0 {
+            progress(added + 1);
+        }
+    }
+    progress(vectors.len());
+    Ok(index)
+}
+
+/// Makes a rebuilt index the database's index, in place of `index`. The new
+/// settings are committed first and the file saved after. Should the process
+/// die in between, the file lags behind the revision and the next start
+/// rebuilds it with the new settings; should the save fail, the flusher
+/// retries it.
+async fn install_index(
+    db_pool: &Pool,
+    database_id: &DatabaseId,
+    settings: &DatabaseSettings,
+    index: &mut SharedIndex,
+    rebuilt: SharedIndex,
+) -> Result<(), actix_web::Error> {
+    let id = database_id.to_string();
+    let settings = settings.clone();
+    db_pool.conn(move |conn| {
+        conn.execute(
+            "UPDATE databases SET metric = ?, quantization = ?, connectivity = ?, expansion_add = ?, expansion_search = ?,
+                 updated_at = ?, revision = revision + 1, generation = random()
+             WHERE database_id = ?",
+            params![
+                settings.metric.as_str(),
+                settings.quantization.as_str(),
+                settings.connectivity as i64,
+                settings.expansion_add as i64,
+                settings.expansion_search as i64,
+                unix_timestamp(),
+                id,
+            ],
+        )
+    }).await.map_err(actix_web::error::ErrorInternalServerError)?;
+
+    *index = rebuilt;
+    index.mark_dirty();
+    if let Err(error) = save_index(db_pool, database_id, index).await {
+        warn!("Failed to save rebuilt index {}, the flusher will retry: {}", database_id, error);
+    }
+    Ok(())
+}
+
+#[api_operation(summary = "Rebuild a database's vector index from the stored embeddings")]
+async fn rebuild_index(
+    app_state: web::Data<Arc<AppState>>,
+    database_id: web::Path<String>,
+    options: web::Json<DatabaseOptions>,
+) -> actix_web::Result<HttpResponse> {
+    let database_id: DatabaseId = database_id.parse().map_err(actix_web::error::ErrorBadRequest)?;
+    let current = require_settings(&app_state.db_pool, &database_id).await?;
+    let settings = rebuild_settings(&current, options.into_inner())?;
+
+    let handle = open_index(&app_state, &database_id, &current)?;
+    let mut index = handle.write().await;
+    let vectors = collect_vectors(&app_state.db_pool, &database_id, settings.dimensions, &index).await?;
+    let build_settings = settings.clone();
+    let rebuilt = web::block(move || {
+        build_index(&build_settings, &vectors, |_| {}).map(SharedIndex::new).map_err(|error| error.to_string())
+    }).await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    install_index(&app_state.db_pool, &database_id, &settings, &mut index, rebuilt).await?;
+    drop(index);
+    app_state.indexes.evict_over_budget();
+
+    let Some(record) = load_databases(&app_state.db_pool, Some(database_id.clone())).await?.pop() else {
+        return Err(actix_web::error::ErrorNotFound(format!("database {} does not exist", database_id)));
+    };
+    Ok(HttpResponse::Ok().json(describe(&app_state, record).await?))
+}
+
+/// Rebuilds the index of a database whose last writes never reached its
+/// index file, from the stored embeddings.
+async fn recover_index(app_state: &AppState, database_id: &DatabaseId) -> Result<(), actix_web::Error> {
+    let settings = require_settings(&app_state.db_pool, database_id).await?;
+    let handle = open_index(app_state, database_id, &settings)?;
+    let mut index = handle.write().await;
+    let vectors = collect_vectors(&app_state.db_pool, database_id, settings.dimensions, &index).await?;
+    *index = SharedIndex::new(build_index(&settings, &vectors, |_| {})?);
+    index.mark_dirty();
+    save_index(&app_state.db_pool, database_id, &index).await
+}
+
+/// Recovers the indexes left behind by a crash before they were flushed.
+async fn recover_indexes(app_state: &AppState) {
+    let stale = app_state.db_pool.conn(|conn| {
+        let mut statement = conn.prepare("SELECT database_id FROM databases WHERE flushed_revision != revision")?;
+        let ids = statement.query_map([], |row| row.get::<_, String>(0))?;
+        ids.collect::<Result<Vec<_>, _>>()
+    }).await;
+    let stale = match stale {
+        Ok(stale) => stale,
+        Err(error) => {
+            warn!("Failed to look for unsaved indexes: {}", error);
+            return;
         }
+    };
+
+    for database_id in stale.iter().filter_map(|id| id.parse::<DatabaseId>().ok()) {
+        warn!("Index {} was not saved before shutdown, rebuilding it", database_id);
+        if let Err(error) = recover_index(app_state, &database_id).await {
+            warn!("Failed to rebuild index {}, run `memista verify --repair {}`: {}", database_id, database_id, error);
+        }
+    }
+}
+
+/// Current version of every chunk of a database.
+async fn load_versions(db_pool: &Pool, database_id: &DatabaseId) -> Result<HashMap<i64, i64>, actix_web::Error> {
+    let table_name = database_id.table_name();
+    db_pool.conn(move |conn| {
+        let mut statement = conn.prepare(&format!("SELECT chunk_id, version FROM {}", table_name))?;
+        let versions = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        versions.collect()
+    }).await.map_err(actix_web::error::ErrorInternalServerError)
+}
 
-        all_results.push(ranked_chunks);
+/// Rebuilds a database's index with `settings` while the current index keeps
+/// serving searches and writes. The new index is built from a snapshot of the
+/// stored embeddings; chunks inserted, updated or deleted meanwhile are found
+/// by their versions and applied to it under the write lock before the swap.
+async fn run_reindex(
+    app_state: &Arc<AppState>,
+    job_id: Uuid,
+    database_id: &DatabaseId,
+    current: &DatabaseSettings,
+    settings: &DatabaseSettings,
+    generation: i64,
+) -> Result<(), actix_web::Error> {
+    // Holding the handle also keeps the index from being evicted and reloaded.
+    let handle = open_index(app_state, database_id, current)?;
+
+    // Writers hold the write lock across their SQLite writes, so under the
+    // read lock versions and embeddings are one snapshot.
+    let (versions, vectors) = {
+        let index = handle.read().await;
+        let versions = load_versions(&app_state.db_pool, database_id).await?;
+        let vectors = collect_vectors(&app_state.db_pool, database_id, settings.dimensions, &index).await?;
+        (versions, vectors)
+    };
+    app_state.jobs.update(job_id, |job| {
+        job.state = JobState::Building;
+        job.total = vectors.len();
+    });
+
+    let state = app_state.clone();
+    let build_settings = settings.clone();
+    let rebuilt = web::block(move || {
+        build_index(&build_settings, &vectors, |processed| state.jobs.update(job_id, |job| job.processed = processed))
+            .map(SharedIndex::new)
+            .map_err(|error| error.to_string())
+    }).await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    app_state.jobs.update(job_id, |job| job.state = JobState::CatchingUp);
+    let mut index = handle.write().await;
+    // A drop removes the row and a rebuild or a recreated database draws a new
+    // generation before either touches the index.
+    if load_generation(&app_state.db_pool, database_id).await? != Some(generation) {
+        return Err(actix_web::error::ErrorConflict(format!(
+            "database {} was dropped or rebuilt during the reindex",
+            database_id
+        )));
+    }
+
+    let live_versions = load_versions(&app_state.db_pool, database_id).await?;
+    for chunk_id in versions.keys().filter(|chunk_id| !live_versions.contains_key(chunk_id)) {
+        rebuilt.remove(*chunk_id as u64).map_err(actix_web::error::ErrorInternalServerError)?;
+    }
+    let changed: Vec<i64> = live_versions.iter()
+        .filter(|(chunk_id, version)| versions.get(chunk_id) != Some(version))
+        .map(|(chunk_id, _)| *chunk_id)
+        .collect();
+    rebuilt.reserve(rebuilt.size() + changed.len()).map_err(actix_web::error::ErrorInternalServerError)?;
+    for (chunk_id, embedding) in load_embeddings(&app_state.db_pool, database_id, Some(changed), settings.dimensions).await? {
+        // Missing embeddings were stored while loading and writes store theirs.
+        let embedding = embedding.ok_or_else(|| {
+            actix_web::error::ErrorInternalServerError(format!("chunk {} has no stored embedding", chunk_id))
+        })?;
+        rebuilt.remove(chunk_id as u64)
+            .and_then(|_| rebuilt.add(chunk_id as u64, &embedding))
+            .map_err(actix_web::error::ErrorInternalServerError)?;
     }
 
-    Ok(HttpResponse::Ok().json(all_results))
+    install_index(&app_state.db_pool, database_id, settings, &mut index, rebuilt).await?;
+    drop(index);
+    app_state.indexes.evict_over_budget();
+    Ok(())
+}
+
+#[api_operation(summary = "Rebuild a database's vector index in the background")]
+async fn reindex(
+    app_state: web::Data<Arc<AppState>>,
+    database_id: web::Path<String>,
+    options: web::Json<DatabaseOptions>,
+) -> actix_web::Result<HttpResponse> {
+    let database_id: DatabaseId = database_id.parse().map_err(actix_web::error::ErrorBadRequest)?;
+    let current = require_settings(&app_state.db_pool, &database_id).await?;
+    let settings = rebuild_settings(&current, options.into_inner())?;
+    let generation = load_generation(&app_state.db_pool, &database_id).await?
+        .ok_or_else(|| actix_web::error::ErrorNotFound(format!("database {} does not exist", database_id)))?;
+
+    let job = app_state.jobs.start(&database_id, unix_timestamp()).map_err(|job_id| {
+        actix_web::error::ErrorConflict(format!("database {} is already being reindexed by job {}", database_id, job_id))
+    })?;
+
+    let app_state = app_state.get_ref().clone();
+    let job_id = job.job_id;
+    actix_web::rt::spawn(async move {
+        let result = run_reindex(&app_state, job_id, &database_id, &current, &settings, generation).await;
+        if let Err(error) = &result {
+            warn!("Reindex of {} failed: {}", database_id, error);
+        }
+        app_state.jobs.finish(job_id, result.map_err(|error| error.to_string()), unix_timestamp());
+    });
+
+    Ok(HttpResponse::Accepted().json(job))
+}
+
+#[api_operation(summary = "Get the status of a background job")]
+async fn get_job(
+    app_state: web::Data<Arc<AppState>>,
+    job_id: web::Path<String>,
+) -> actix_web::Result<HttpResponse> {
+    let job_id: Uuid = job_id.parse().map_err(actix_web::error::ErrorBadRequest)?;
+    let job = app_state.jobs.get(job_id)
+        .ok_or_else(|| actix_web::error::ErrorNotFound(format!("job {} does not exist", job_id)))?;
+    Ok(HttpResponse::Ok().json(job))
 }
 
 #[api_operation(summary = "Drop a table for a specific database")]
@@ -184,16 +2445,29 @@ async fn drop_table(
     app_state: web::Data<Arc<AppState>>,
     request: web::Json<DropTableRequest>,
 ) -> actix_web::Result<HttpResponse> {
-    let table_name = format!("chunks_{}", request.database_id);
+    let table_name = request.database_id.table_name();
+    let text_index_table = full_text::text_index_table(&request.database_id);
+    let database_id = request.database_id.to_string();
     
     app_state.db_pool.conn(move |conn| {
+        conn.execute(
+            &format!("DROP TABLE IF EXISTS {}", text_index_table),
+            [],
+        )?;
         conn.execute(
             &format!("DROP TABLE IF EXISTS {}", table_name),
             [],
-        )
+        )?;
+        conn.execute("DELETE FROM databases WHERE database_id = ?", [&database_id])
     }).await.map_err(actix_web::error::ErrorInternalServerError)?;
     
-    let index_file = format!("{}.usearch", request.database_id);
+    // Wait for in-flight writers, then discard their changes so the flusher
+    // does not save the index back afterwards.
+    if let Some(handle) = app_state.indexes.remove(request.database_id.as_str()) {
+        handle.write().await.take_dirty();
+    }
+
+    let index_file = request.database_id.index_file();
     if std::path::Path::new(&index_file).exists() {
         std::fs::remove_file(index_file).map_err(actix_web::error::ErrorInternalServerError)?;
     }
@@ -207,6 +2481,8 @@ struct Config {
     server_host: String,
     server_port: u16,
     log_level: String,
+    index_memory_budget: usize,
+    index_flush_interval: u64,
 }
 
 impl Config {
@@ -219,10 +2495,61 @@ impl Config {
                 .parse()
                 .expect("SERVER_PORT must be a number"),
             log_level: env::var("LOG_LEVEL").unwrap_or_else(|_| "info".to_string()),
+            index_memory_budget: env::var("INDEX_MEMORY_BUDGET")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("INDEX_MEMORY_BUDGET must be a number"),
+            index_flush_interval: env::var("INDEX_FLUSH_INTERVAL")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse()
+                .expect("INDEX_FLUSH_INTERVAL must be a number"),
         })
     }
 }
 
+/// Runs `memista verify [--repair] [database_id...]`, checking every database
+/// when none is named. Prints a JSON report per database and returns the exit
+/// code, non-zero if a database could not be checked or is left inconsistent.
+async fn run_verify(app_state: &AppState, args: &[String]) -> i32 {
+    let repair = args.iter().any(|arg| arg == "--repair");
+    let mut database_ids = Vec::new();
+    for arg in args.iter().filter(|arg| *arg != "--repair") {
+        match arg.parse::<DatabaseId>() {
+            Ok(database_id) => database_ids.push(database_id),
+            Err(error) => {
+                eprintln!("{}: {}", arg, error);
+                return 2;
+            }
+        }
+    }
+    if database_ids.is_empty() {
+        match load_databases(&app_state.db_pool, None).await {
+            Ok(records) => database_ids = records.into_iter().map(|record| record.database_id).collect(),
+            Err(error) => {
+                eprintln!("{}", error);
+                return 1;
+            }
+        }
+    }
+
+    let mut code = 0;
+    for database_id in &database_ids {
+        match verify_database(app_state, database_id, repair).await {
+            Ok(report) => {
+                println!("{}", serde_json::to_string_pretty(&report).expect("reports serialize"));
+                if !report.consistent {
+                    code = 1;
+                }
+            }
+            Err(error) => {
+                eprintln!("{}: {}", database_id, error);
+                code = 1;
+            }
+        }
+    }
+    code
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // Load .env file
@@ -242,15 +2569,51 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Failed to create database pool");
 
+    ensure_databases_table(&db_pool)
+        .await
+        .expect("Failed to create databases table");
+
+    lowercase_database_ids(&db_pool)
+        .await
+        .expect("Failed to lowercase database ids");
+
+    adopt_legacy_databases(&db_pool)
+        .await
+        .expect("Failed to adopt legacy databases");
+
+    upgrade_chunk_tables(&db_pool)
+        .await
+        .expect("Failed to upgrade chunk tables");
+
     let app_state = Arc::new(AppState {
         db_pool,
+        indexes: IndexRegistry::new(config.index_memory_budget),
+        jobs: JobRegistry::default(),
     });
 
+    recover_indexes(&app_state).await;
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("verify") {
+        std::process::exit(run_verify(&app_state, &args[1..]).await);
+    }
+
     let bind_address = format!("{}:{}", config.server_host, config.server_port);
     
     info!("Starting server on {}", bind_address);
-    
-    HttpServer::new(move || {
+
+    let flush_interval = Duration::from_secs(config.index_flush_interval.max(1));
+    let flushed_state = app_state.clone();
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(flush_interval);
+        loop {
+            interval.tick().await;
+            flush_indexes(&flushed_state).await;
+        }
+    });
+
+    let server_state = app_state.clone();
+    let result = HttpServer::new(move || {
         let spec = Spec {
             info: Info {
                 title: "Vector Search API".to_string(),
@@ -265,12 +2628,27 @@ async fn main() -> std::io::Result<()> {
         };
 
         App::new()
-            .app_data(web::Data::new(app_state.clone()))
+            .app_data(web::Data::new(server_state.clone()))
             .document(spec)
             .service(scope("/v1")
+                .service(resource("/databases")
+                    .route(post().to(create_database))
+                    .route(get().to(list_databases)))
+                .service(resource("/databases/{database_id}").route(get().to(describe_database)))
+                .service(resource("/databases/{database_id}/verify").route(post().to(verify)))
+                .service(resource("/databases/{database_id}/rebuild").route(post().to(rebuild_index)))
+                .service(resource("/databases/{database_id}/reindex").route(post().to(reindex)))
+                .service(resource("/databases/{database_id}/chunks/batch").route(post().to(get_chunks)))
+                .service(resource("/databases/{database_id}/chunks/delete").route(post().to(delete_chunks)))
+                .service(resource("/databases/{database_id}/chunks/{chunk_id}")
+                    .route(get().to(get_chunk))
+                    .route(put().to(update_chunk))
+                    .route(delete().to(delete_chunk)))
                 .service(resource("/insert").route(post().to(insert_chunk)))
                 .service(resource("/search").route(post().to(search)))
+                .service(resource("/search/text").route(post().to(text_search)))
                 .service(resource("/drop").route(delete().to(drop_table)))
+                .service(resource("/jobs/{job_id}").route(get().to(get_job)))
             )
             .build_with(
                 "/openapi.json",
@@ -283,5 +2661,93 @@ async fn main() -> std::io::Result<()> {
     })
     .bind(bind_address)?
     .run()
-    .await
+    .await;
+
+    flush_indexes(&app_state).await;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_grow_with_similarity() {
+        let (a, b) = ([0.6, 0.8, 0.0, 0.0], [0.8, 0.6, 0.0, 0.0]);
+        assert!((Metric::Cosine.distance(&a, &b) - 0.04).abs() < 1e-6);
+        assert!((Metric::InnerProduct.distance(&a, &b) - 0.04).abs() < 1e-6);
+        assert!((Metric::L2.distance(&a, &b) - 0.08).abs() < 1e-6);
+        assert_eq!(Metric::Cosine.distance(&a, &[0.0; 4]), 1.0);
+
+        let (x, y) = ([1.0, 1.0, 0.0, 0.0], [1.0, 0.0, 1.0, 0.0]);
+        assert_eq!(Metric::Hamming.distance(&x, &y), 2.0);
+        assert!((Metric::Jaccard.distance(&x, &y) - 2.0 / 3.0).abs() < 1e-6);
+        assert_eq!(Metric::Jaccard.distance(&[0.0; 4], &[0.0; 4]), 0.0);
+
+        assert_eq!(Metric::Cosine.score(0.25), 0.75);
+        assert_eq!(Metric::L2.score(0.25), -0.25);
+        for metric in [Metric::Cosine, Metric::L2, Metric::InnerProduct, Metric::Hamming, Metric::Jaccard] {
+            assert!(metric.score(0.0) > metric.score(1.0), "{:?}", metric);
+        }
+    }
+
+    #[test]
+    fn distances_match_usearch() {
+        let cases = [
+            (Metric::Cosine, vec![0.6, 0.8, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0], vec![0.8, 0.6, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            (Metric::L2, vec![1.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0], vec![0.0, 0.5, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            (Metric::InnerProduct, vec![0.6, 0.8, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0], vec![0.8, 0.6, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            (Metric::Hamming, vec![1.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0], vec![1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0]),
+            (Metric::Jaccard, vec![1.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0], vec![1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0]),
+        ];
+        for (metric, stored, query) in cases {
+            let settings = DatabaseSettings {
+                dimensions: stored.len(),
+                metric,
+                quantization: Quantization::default_for(metric),
+                connectivity: 0,
+                expansion_add: 0,
+                expansion_search: 0,
+            };
+            let index = create_index(&settings).unwrap();
+            index.reserve(1).unwrap();
+            index.add(1, &stored).unwrap();
+            let found = search_index(&index, &settings, std::slice::from_ref(&query), 1, None).unwrap();
+            assert!((found[0].distances[0] - metric.distance(&stored, &query)).abs() < 1e-5, "{:?}", metric);
+        }
+    }
+
+    #[test]
+    fn embeddings_round_trip_through_blobs() {
+        let embedding = [0.5, -1.25, f32::MAX, 0.0];
+        let blob = embedding_to_blob(&embedding);
+        assert_eq!(blob.len(), 16);
+        assert_eq!(embedding_from_blob(&blob, 4), Some(embedding.to_vec()));
+        assert_eq!(embedding_from_blob(&blob, 3), None);
+        assert_eq!(embedding_from_blob(&blob[..15], 4), None);
+    }
+
+    #[test]
+    fn rebuilds_keep_unchanged_settings() {
+        let current = DatabaseSettings {
+            dimensions: 8,
+            metric: Metric::Cosine,
+            quantization: Quantization::F16,
+            connectivity: 32,
+            expansion_add: 0,
+            expansion_search: 64,
+        };
+        let kept = rebuild_settings(&current, DatabaseOptions { expansion_add: Some(128), ..Default::default() }).unwrap();
+        assert_eq!((kept.dimensions, kept.metric, kept.quantization), (8, Metric::Cosine, Quantization::F16));
+        assert_eq!((kept.connectivity, kept.expansion_add, kept.expansion_search), (32, 128, 64));
+
+        // A new metric without a quantization takes that metric's default
+        // rather than the current quantization.
+        let binary = rebuild_settings(&current, DatabaseOptions { metric: Some(Metric::Hamming), ..Default::default() }).unwrap();
+        assert_eq!((binary.metric, binary.quantization), (Metric::Hamming, Quantization::B1));
+        let back = rebuild_settings(&binary, DatabaseOptions { metric: Some(Metric::L2), ..Default::default() }).unwrap();
+        assert_eq!((back.metric, back.quantization), (Metric::L2, Quantization::F32));
+
+        assert!(rebuild_settings(&current, DatabaseOptions { quantization: Some(Quantization::B1), ..Default::default() }).is_err());
+    }
 }
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::CANDIDATES_PER_RESULT;
+
+/// Largest `candidates` a request may ask for.
+pub const MAX_CANDIDATES: usize = 40_000;
+
+fn default_lambda() -> f32 {
+    0.5
+}
+
+/// Maximal marginal relevance re-ranking, which trades some relevance for
+/// hits that are not near-duplicates of each other.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Mmr {
+    /// Weight of relevance to the query against dissimilarity to the hits
+    /// already picked, between 0 and 1. `1` keeps the plain ranking.
+    #[serde(default = "default_lambda")]
+    pub lambda: f32,
+    /// Number of top candidates to pick the hits from, at least the number
+    /// of hits requested. Defaults to four times that number.
+    #[serde(default)]
+    pub candidates: Option<usize>,
+}
+
+impl Mmr {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.lambda) {
+            return Err("mmr lambda must be between 0 and 1".to_string());
+        }
+        if self.candidates.is_some_and(|candidates| candidates > MAX_CANDIDATES) {
+            return Err(format!("mmr candidates must be at most {}", MAX_CANDIDATES));
+        }
+        Ok(())
+    }
+
+    /// Size of the candidate pool when `count` hits are wanted.
+    pub fn pool_size(&self, count: usize) -> usize {
+        self.candidates.unwrap_or(count.saturating_mul(CANDIDATES_PER_RESULT)).max(count)
+    }
+
+    /// Picks up to `count` of `candidates`, given as `(chunk_id, score)`, one
+    /// at a time, each maximising `lambda * relevance - (1 - lambda) *
+    /// redundancy`. Relevance is the similarity of a candidate's vector to
+    /// `query` and redundancy its highest similarity to a picked one.
+    /// Candidates without a vector follow the others in their original order.
+    pub fn rerank(
+        &self,
+        candidates: &[(u64, f32)],
+        vectors: &HashMap<u64, Vec<f32>>,
+        query: &[f32],
+        similarity: impl Fn(&[f32], &[f32]) -> f32,
+        count: usize,
+    ) -> Vec<(u64, f32)> {
+        let (pending, missing): (Vec<_>, Vec<_>) =
+            candidates.iter().partition(|(chunk_id, _)| vectors.contains_key(chunk_id));
+        let relevance: Vec<f32> = pending.iter().map(|(chunk_id, _)| similarity(query, &vectors[chunk_id])).collect();
+        let mut redundancy: Vec<Option<f32>> = vec![None; pending.len()];
+        let mut remaining: Vec<usize> = (0..pending.len()).collect();
+
+        let mut picked = Vec::new();
+        while picked.len() < count && !remaining.is_empty() {
+            let (position, _) = remaining
+                .iter()
+                .enumerate()
+                .map(|(position, &candidate)| {
+                    let penalty = redundancy[candidate].unwrap_or(0.0);
+                    (position, self.lambda * relevance[candidate] - (1.0 - self.lambda) * penalty)
+                })
+                // Prefer the better ranked candidate on ties.
+                .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+                .unwrap();
+            let chosen = remaining.remove(position);
+            let chosen_vector = &vectors[&pending[chosen].0];
+            for &candidate in &remaining {
+                let similar = similarity(&vectors[&pending[candidate].0], chosen_vector);
+                redundancy[candidate] = Some(redundancy[candidate].map_or(similar, |current| current.max(similar)));
+            }
+            picked.push(pending[chosen]);
+        }
+
+        picked.extend(missing.into_iter().take(count - picked.len()));
+        picked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn skips_near_duplicates() {
+        let vectors: HashMap<u64, Vec<f32>> = [
+            (1, vec![1.0, 0.0]),
+            (2, vec![0.99, 0.14]),
+            (3, vec![0.0, 1.0]),
+        ]
+        .into_iter()
+        .collect();
+        let query = [0.8, 0.6];
+        let candidates = [(2, 0.876), (1, 0.8), (3, 0.6)];
+        let ids = |mmr: Mmr| -> Vec<u64> {
+            mmr.rerank(&candidates, &vectors, &query, dot, 2).into_iter().map(|(chunk_id, _)| chunk_id).collect()
+        };
+
+        assert_eq!(ids(Mmr { lambda: 1.0, candidates: None }), vec![2, 1]);
+        assert_eq!(ids(Mmr { lambda: 0.5, candidates: None }), vec![2, 3]);
+    }
+
+    #[test]
+    fn keeps_candidates_without_vectors_last() {
+        let vectors: HashMap<u64, Vec<f32>> = [(2, vec![1.0, 0.0])].into_iter().collect();
+        let mmr = Mmr { lambda: 0.5, candidates: None };
+        let picked = mmr.rerank(&[(1, 0.9), (2, 0.8)], &vectors, &[1.0, 0.0], dot, 5);
+        assert_eq!(picked, vec![(2, 0.8), (1, 0.9)]);
+        assert_eq!(mmr.pool_size(3), 12);
+        assert_eq!(Mmr { lambda: 0.5, candidates: Some(1) }.pool_size(3), 3);
+        assert_eq!(mmr.pool_size(usize::MAX), usize::MAX);
+    }
+
+    #[test]
+    fn rejects_out_of_range_settings() {
+        assert!(Mmr { lambda: 1.5, candidates: None }.validate().is_err());
+        assert!(Mmr { lambda: 0.5, candidates: Some(MAX_CANDIDATES + 1) }.validate().is_err());
+        assert!(Mmr { lambda: 0.5, candidates: Some(MAX_CANDIDATES) }.validate().is_ok());
+    }
+}
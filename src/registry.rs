@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use log::info;
+use tokio::sync::RwLock;
+use usearch::Index;
+
+/// A usearch index that can be shared between worker threads.
+///
+/// The native index synchronises concurrent `add` and `search` calls itself,
+/// but the generated bindings do not mark it `Send`/`Sync`.
+pub struct SharedIndex {
+    index: Index,
+    /// Whether the index has changes its file does not have yet.
+    dirty: AtomicBool,
+}
+
+impl SharedIndex {
+    pub fn new(index: Index) -> Self {
+        SharedIndex { index, dirty: AtomicBool::new(false) }
+    }
+
+    /// Records a change to be saved by the next flush.
+    pub fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::Acquire)
+    }
+
+    /// Clears the dirty flag, returning whether it was set.
+    pub fn take_dirty(&self) -> bool {
+        self.dirty.swap(false, Ordering::AcqRel)
+    }
+}
+
+unsafe impl Send for SharedIndex {}
+unsafe impl Sync for SharedIndex {}
+
+impl Deref for SharedIndex {
+    type Target = Index;
+
+    fn deref(&self) -> &Index {
+        &self.index
+    }
+}
+
+/// Handle to an open index. Readers search under the read lock; writers hold
+/// the write lock across their SQLite and index changes so they are not
+/// interleaved, and mark the index dirty for the flusher to save.
+pub type IndexHandle = Arc<RwLock<SharedIndex>>;
+
+struct Entry {
+    index: IndexHandle,
+    last_used: u64,
+    memory_usage: usize,
+}
+
+/// Open indexes keyed by database id, evicted least recently used first once
+/// their combined memory usage exceeds the budget.
+pub struct IndexRegistry {
+    entries: Mutex<HashMap<String, Entry>>,
+    clock: AtomicU64,
+    /// Maximum bytes of resident indexes, `0` for no limit.
+    memory_budget: usize,
+}
+
+impl IndexRegistry {
+    pub fn new(memory_budget: usize) -> Self {
+        IndexRegistry {
+            entries: Mutex::new(HashMap::new()),
+            clock: AtomicU64::new(0),
+            memory_budget,
+        }
+    }
+
+    /// Returns the open index of `database_id`, calling `open` to load it if it
+    /// is not resident.
+    pub fn get_or_open<E>(
+        &self,
+        database_id: &str,
+        open: impl FnOnce() -> Result<Index, E>,
+    ) -> Result<IndexHandle, E> {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(database_id) {
+            entry.last_used = self.tick();
+            return Ok(entry.index.clone());
+        }
+
+        // Load outside the lock so other databases stay available meanwhile.
+        let index = open()?;
+        let memory_usage = index.memory_usage();
+
+        let handle = {
+            let mut entries = self.entries.lock().unwrap();
+            let last_used = self.tick();
+            let entry = entries.entry(database_id.to_string()).or_insert_with(|| Entry {
+                index: Arc::new(RwLock::new(SharedIndex::new(index))),
+                last_used,
+                memory_usage,
+            });
+            entry.last_used = last_used;
+            entry.index.clone()
+        };
+
+        self.evict_over_budget();
+        Ok(handle)
+    }
+
+    /// Returns the index of `database_id` if it is resident, without counting
+    /// as a use.
+    pub fn get(&self, database_id: &str) -> Option<IndexHandle> {
+        self.entries.lock().unwrap().get(database_id).map(|entry| entry.index.clone())
+    }
+
+    /// Forgets the index of `database_id`, returning it if it was resident.
+    pub fn remove(&self, database_id: &str) -> Option<IndexHandle> {
+        self.entries.lock().unwrap().remove(database_id).map(|entry| entry.index)
+    }
+
+    /// Open indexes with their database ids.
+    pub fn resident(&self) -> Vec<(String, IndexHandle)> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter().map(|(database_id, entry)| (database_id.clone(), entry.index.clone())).collect()
+    }
+
+    /// Unloads idle indexes, least recently used first, until the resident
+    /// ones fit in the memory budget. Dirty indexes stay until they are saved,
+    /// so nothing is lost by dropping them.
+    pub fn evict_over_budget(&self) {
+        if self.memory_budget == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        for entry in entries.values_mut() {
+            if let Ok(index) = entry.index.try_read() {
+                entry.memory_usage = index.memory_usage();
+            }
+        }
+
+        let mut total: usize = entries.values().map(|entry| entry.memory_usage).sum();
+        if total <= self.memory_budget {
+            return;
+        }
+
+        // Handles only leave the registry under this lock, so an index nobody
+        // else references cannot be picked up while we evict it.
+        let mut idle: Vec<(u64, String)> = entries
+            .iter()
+            .filter(|(_, entry)| {
+                Arc::strong_count(&entry.index) == 1
+                    && entry.index.try_read().map(|index| !index.is_dirty()).unwrap_or(false)
+            })
+            .map(|(database_id, entry)| (entry.last_used, database_id.clone()))
+            .collect();
+        idle.sort();
+
+        for (_, database_id) in idle {
+            if total <= self.memory_budget {
+                break;
+            }
+            if let Some(entry) = entries.remove(&database_id) {
+                total -= entry.memory_usage;
+                info!("Evicted index {} ({} bytes)", database_id, entry.memory_usage);
+            }
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Barrier;
+
+    use usearch::IndexOptions;
+
+    use super::*;
+
+    /// A small index. Empty ones report no memory usage, so it holds a vector.
+    fn index() -> Index {
+        let index = Index::new(&IndexOptions { dimensions: 2, ..Default::default() }).unwrap();
+        index.reserve(1).unwrap();
+        index.add(1, &[1.0, 0.0]).unwrap();
+        index
+    }
+
+    fn open(registry: &IndexRegistry, database_id: &str) -> IndexHandle {
+        registry.get_or_open(database_id, || Ok::<_, ()>(index())).unwrap()
+    }
+
+    fn resident(registry: &IndexRegistry) -> Vec<String> {
+        let mut ids: Vec<String> = registry.resident().into_iter().map(|(database_id, _)| database_id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn evicts_least_recently_used_first() {
+        let registry = IndexRegistry::new(2 * index().memory_usage());
+        open(&registry, "a");
+        open(&registry, "b");
+        open(&registry, "a");
+        // Peeking does not count as a use.
+        registry.get("b");
+        open(&registry, "c");
+        assert_eq!(resident(&registry), ["a", "c"]);
+    }
+
+    #[test]
+    fn keeps_dirty_indexes_until_saved() {
+        let registry = IndexRegistry::new(index().memory_usage());
+        open(&registry, "a").try_read().unwrap().mark_dirty();
+        open(&registry, "b");
+        assert_eq!(resident(&registry), ["a", "b"]);
+
+        registry.get("a").unwrap().try_read().unwrap().take_dirty();
+        registry.evict_over_budget();
+        assert_eq!(resident(&registry), ["b"]);
+    }
+
+    #[test]
+    fn keeps_indexes_in_use() {
+        let registry = IndexRegistry::new(index().memory_usage());
+        let a = open(&registry, "a");
+        open(&registry, "b");
+        assert_eq!(resident(&registry), ["a", "b"]);
+
+        drop(a);
+        registry.evict_over_budget();
+        assert_eq!(resident(&registry), ["b"]);
+    }
+
+    #[test]
+    fn zero_budget_is_unlimited() {
+        let registry = IndexRegistry::new(0);
+        for database_id in ["a", "b", "c", "d"] {
+            open(&registry, database_id);
+        }
+        assert_eq!(resident(&registry), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn concurrent_opens_share_one_index() {
+        let registry = IndexRegistry::new(0);
+        // Both threads are loading before either registers its index.
+        let loading = Barrier::new(2);
+        let open = || {
+            registry.get_or_open("a", || {
+                loading.wait();
+                Ok::<_, ()>(index())
+            }).unwrap()
+        };
+        let (first, second) = std::thread::scope(|scope| {
+            let first = scope.spawn(open);
+            let second = scope.spawn(open);
+            (first.join().unwrap(), second.join().unwrap())
+        });
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(resident(&registry), ["a"]);
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashSet;
+
+/// Rows an insert batch changed, recorded as it writes them so that a batch
+/// whose vectors cannot be added can be taken back out of the chunk table.
+#[derive(Debug)]
+pub struct RowUndo<R> {
+    /// Chunks the batch created, to delete.
+    pub created: Vec<i64>,
+    /// Rows as they were before the batch overwrote them, to put back.
+    pub overwritten: Vec<R>,
+    seen: HashSet<i64>,
+}
+
+impl<R> RowUndo<R> {
+    pub fn new() -> Self {
+        RowUndo { created: Vec::new(), overwritten: Vec::new(), seen: HashSet::new() }
+    }
+
+    /// Records a write of `chunk_id`, whose row was `previous` before it. Only
+    /// the first write of a chunk counts: later chunks of the batch with the
+    /// same external id find the row the batch itself wrote.
+    pub fn record(&mut self, chunk_id: i64, previous: Option<R>) {
+        if !self.seen.insert(chunk_id) {
+            return;
+        }
+        match previous {
+            Some(row) => self.overwritten.push(row),
+            None => self.created.push(chunk_id),
+        }
+    }
+}
+
+/// How adding the vector of one chunk of a batch changes the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorStep {
+    /// The key is new to the index.
+    Add,
+    /// The key holds a vector from before the batch, which has to be kept to
+    /// be put back.
+    Replace,
+    /// An earlier chunk of the batch added the key. Its vector is dropped, as
+    /// the later chunk wins.
+    Supersede,
+}
+
+/// Keys an insert batch added to the index, to remove them again.
+#[derive(Debug, Default)]
+pub struct VectorUndo {
+    pub added: HashSet<u64>,
+}
+
+impl VectorUndo {
+    /// Decides how to add the vector under `key`, given whether the index
+    /// held the key before this step, and records the key as added.
+    pub fn step(&mut self, key: u64, in_index: bool) -> VectorStep {
+        let step = if self.added.contains(&key) {
+            VectorStep::Supersede
+        } else if in_index {
+            VectorStep::Replace
+        } else {
+            VectorStep::Add
+        };
+        self.added.insert(key);
+        step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_rows_as_they_were_before_the_batch() {
+        let mut undo = RowUndo::new();
+        undo.record(1, None);
+        undo.record(2, Some("old"));
+        undo.record(1, Some("written by the batch"));
+        undo.record(2, Some("written by the batch"));
+        assert_eq!((undo.created, undo.overwritten), (vec![1], vec!["old"]));
+    }
+
+    #[test]
+    fn replaces_only_vectors_from_before_the_batch() {
+        let mut undo = VectorUndo::default();
+        let steps = [(1, false), (2, true), (1, true), (2, true)].map(|(key, in_index)| undo.step(key, in_index));
+        assert_eq!(steps, [VectorStep::Add, VectorStep::Replace, VectorStep::Supersede, VectorStep::Supersede]);
+        assert_eq!(undo.added, [1, 2].into_iter().collect());
+    }
+}